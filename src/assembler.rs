@@ -0,0 +1,322 @@
+use opcode::Opcode;
+use register::*;
+use std::collections::HashMap;
+use std::fmt;
+
+const PROGRAM_START: u16 = 0x200;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+    raw: Vec<u8>,
+}
+
+/// Assembles source in the syntax produced by `Opcode::to_asm` into a ROM image.
+///
+/// Lines may start with a `name:` label and `;` starts a comment. Addresses are
+/// written as `$2A4` or a label, immediates as `#$1F`, and `DB`/`DW` emit raw
+/// bytes/words. Lines of a `decompile` listing are accepted as-is.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut labels: HashMap<String, u16> = HashMap::new();
+    let mut statements = vec![];
+    let mut addr = PROGRAM_START as usize;
+
+    for (n, line) in source.lines().enumerate() {
+        let line_no = n + 1;
+        let line = match line.find(';') {
+            Some(i) => &line[..i],
+            None => line,
+        };
+        let (raw, mut line) = strip_listing_prefix(line);
+        line = line.trim();
+
+        if let Some(i) = line.find(':') {
+            let label = line[..i].trim();
+            if !is_label(label) {
+                return Err(AsmError { line: line_no, msg: format!("Invalid label \"{}\"", label) });
+            }
+            if labels.insert(label.to_string(), addr as u16).is_some() {
+                return Err(AsmError { line: line_no, msg: format!("Duplicate label \"{}\"", label) });
+            }
+            line = line[i + 1..].trim();
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        let operands: Vec<&str> = if rest.is_empty() {
+            vec![]
+        } else {
+            rest.split(',').map(|s| s.trim()).collect()
+        };
+        let statement = Statement {
+            line: line_no,
+            mnemonic: mnemonic.to_uppercase(),
+            operands,
+            raw,
+        };
+
+        addr += statement_size(&statement);
+        if addr > 0x10000 {
+            return Err(AsmError { line: line_no, msg: "Program does not fit in memory".to_string() });
+        }
+        statements.push(statement);
+    }
+
+    let mut out = vec![];
+    for statement in statements.iter() {
+        encode_statement(statement, &labels, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Splits the `0200: 12 34   ` prefix off a `decompile` listing line, returning its bytes.
+fn strip_listing_prefix(line: &str) -> (Vec<u8>, &str) {
+    let is_listing = line.len() > 5 && line.get(..4).map_or(false, |a| a.chars().all(|c| c.is_digit(16))) &&
+        line.get(4..5) == Some(":");
+    let split = line.find("   ");
+    if !is_listing || split.is_none() {
+        return (vec![], line);
+    }
+    let split = split.unwrap();
+    let mut raw = vec![];
+    for byte in line[5..split].split_whitespace() {
+        match u8::from_str_radix(byte, 16) {
+            Ok(b) => raw.push(b),
+            Err(_) => return (vec![], line),
+        }
+    }
+    (raw, &line[split..])
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => chars.all(|c| c.is_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+fn statement_size(statement: &Statement) -> usize {
+    match statement.mnemonic.as_ref() {
+        "DB" => statement.operands.len(),
+        "DW" => statement.operands.len() * 2,
+        "[UNDEFINED]" => statement.raw.len(),
+        "LONGI" => 4,
+        _ => 2,
+    }
+}
+
+fn encode_statement(
+    statement: &Statement,
+    labels: &HashMap<String, u16>,
+    out: &mut Vec<u8>,
+) -> Result<(), AsmError> {
+    let err = |msg: String| AsmError { line: statement.line, msg };
+    let ops = &statement.operands;
+
+    match statement.mnemonic.as_ref() {
+        "DB" => {
+            for op in ops.iter() {
+                out.push(parse_value(op, labels, 0xff).map_err(&err)? as u8);
+            }
+            return Ok(());
+        }
+        "DW" => {
+            for op in ops.iter() {
+                let word = parse_value(op, labels, 0xffff).map_err(&err)?;
+                out.push((word >> 8) as u8);
+                out.push((word & 0xff) as u8);
+            }
+            return Ok(());
+        }
+        "[UNDEFINED]" => {
+            out.extend_from_slice(&statement.raw);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let expect = |n: usize| if ops.len() == n {
+        Ok(())
+    } else {
+        Err(err(format!("{} takes {} operand(s), found {}", statement.mnemonic, n, ops.len())))
+    };
+    let reg = |i: usize| parse_vreg(ops[i]).map_err(&err);
+    let addr = |i: usize| parse_value(ops[i], labels, 0xfff).map_err(&err);
+    let byte = |i: usize| parse_value(ops[i], labels, 0xff).map(|v| v as u8).map_err(&err);
+
+    let opcode = match statement.mnemonic.as_ref() {
+        "CLS" => expect(0).map(|_| Opcode::CLS)?,
+        "RET" => expect(0).map(|_| Opcode::RET)?,
+        "JMP" => { expect(1)?; Opcode::JMP(addr(0)?) }
+        "CALL" => { expect(1)?; Opcode::CALL(addr(0)?) }
+        "SKIPEQ" => { expect(2)?; Opcode::SKIPEQ(reg(0)?, byte(1)?) }
+        "SKIPNEQ" => { expect(2)?; Opcode::SKIPNEQ(reg(0)?, byte(1)?) }
+        "SKIPREQ" => { expect(2)?; Opcode::SKIPREQ(reg(0)?, reg(1)?) }
+        "MOV" => { expect(2)?; Opcode::MOV(reg(0)?, byte(1)?) }
+        "ADD" => { expect(2)?; Opcode::ADD(reg(0)?, byte(1)?) }
+        "MOVR" => { expect(2)?; Opcode::MOVR(reg(0)?, reg(1)?) }
+        "OR" => { expect(2)?; Opcode::OR(reg(0)?, reg(1)?) }
+        "AND" => { expect(2)?; Opcode::AND(reg(0)?, reg(1)?) }
+        "XOR" => { expect(2)?; Opcode::XOR(reg(0)?, reg(1)?) }
+        "ADDR" => { expect(2)?; Opcode::ADDR(reg(0)?, reg(1)?) }
+        "SUBR" => { expect(2)?; Opcode::SUBR(reg(0)?, reg(1)?) }
+        "SR" => { expect(2)?; Opcode::SR(reg(0)?, reg(1)?) }
+        "RSUBR" => { expect(2)?; Opcode::RSUBR(reg(0)?, reg(1)?) }
+        "SL" => { expect(2)?; Opcode::SL(reg(0)?, reg(1)?) }
+        "SKIPRNEQ" => { expect(2)?; Opcode::SKIPRNEQ(reg(0)?, reg(1)?) }
+        "SI" => { expect(1)?; Opcode::SI(addr(0)?) }
+        "JMPR" => { expect(1)?; Opcode::JMPR(addr(0)?) }
+        "RAND" => { expect(2)?; Opcode::RAND(reg(0)?, byte(1)?) }
+        "DRAW" => {
+            expect(3)?;
+            let n = parse_value(ops[2], labels, 0xf).map_err(&err)? as u8;
+            Opcode::DRAW(reg(0)?, reg(1)?, n)
+        }
+        "SKIPKEQ" => { expect(1)?; Opcode::SKIPKEQ(reg(0)?) }
+        "SKIPKNEQ" => { expect(1)?; Opcode::SKIPKNEQ(reg(0)?) }
+        "GDELAY" => { expect(1)?; Opcode::GDELAY(reg(0)?) }
+        "GKEY" => { expect(1)?; Opcode::GKEY(reg(0)?) }
+        "SDELAY" => { expect(1)?; Opcode::SDELAY(reg(0)?) }
+        "SSND" => { expect(1)?; Opcode::SSND(reg(0)?) }
+        "ADDI" => { expect(1)?; Opcode::ADDI(reg(0)?) }
+        "SPRITE" => { expect(1)?; Opcode::SPRITE(reg(0)?) }
+        "BCD" => { expect(1)?; Opcode::BCD(reg(0)?) }
+        "RDUMP" => { expect(1)?; Opcode::RDUMP(reg(0)?) }
+        "RLOAD" => { expect(1)?; Opcode::RLOAD(reg(0)?) }
+        "SCD" => {
+            expect(1)?;
+            Opcode::SCD(parse_value(ops[0], labels, 0xf).map_err(&err)? as u8)
+        }
+        "SCR" => expect(0).map(|_| Opcode::SCR)?,
+        "SCL" => expect(0).map(|_| Opcode::SCL)?,
+        "EXIT" => expect(0).map(|_| Opcode::EXIT)?,
+        "LOW" => expect(0).map(|_| Opcode::LOW)?,
+        "HIGH" => expect(0).map(|_| Opcode::HIGH)?,
+        "XSPRITE" => { expect(1)?; Opcode::XSPRITE(reg(0)?) }
+        "SRPL" => { expect(1)?; Opcode::SRPL(reg(0)?) }
+        "LRPL" => { expect(1)?; Opcode::LRPL(reg(0)?) }
+        "LONGI" => {
+            expect(1)?;
+            Opcode::LONGI(parse_value(ops[0], labels, 0xffff).map_err(&err)?)
+        }
+        "SAVER" => { expect(2)?; Opcode::SAVER(reg(0)?, reg(1)?) }
+        "LOADR" => { expect(2)?; Opcode::LOADR(reg(0)?, reg(1)?) }
+        "PLANE" => {
+            expect(1)?;
+            Opcode::PLANE(parse_value(ops[0], labels, 0xf).map_err(&err)? as u8)
+        }
+        "AUDIO" => expect(0).map(|_| Opcode::AUDIO)?,
+        "PITCH" => { expect(1)?; Opcode::PITCH(reg(0)?) }
+        other => return Err(err(format!("Unknown mnemonic \"{}\"", other))),
+    };
+
+    out.extend(opcode.to_bytes());
+    Ok(())
+}
+
+fn parse_vreg(s: &str) -> Result<VReg, String> {
+    let upper = s.to_uppercase();
+    if upper.len() == 2 && upper.starts_with('V') {
+        if let Ok(v) = u8::from_str_radix(&upper[1..], 16) {
+            return Ok(from_int(v).unwrap());
+        }
+    }
+    Err(format!("Expected a register, found \"{}\"", s))
+}
+
+fn parse_value(s: &str, labels: &HashMap<String, u16>, max: u16) -> Result<u16, String> {
+    let literal = if s.starts_with('#') { &s[1..] } else { s };
+    let val = if literal.starts_with('$') {
+        u16::from_str_radix(&literal[1..], 16).ok()
+    } else if literal.chars().next().map_or(false, |c| c.is_digit(10)) {
+        literal.parse::<u16>().ok()
+    } else {
+        match labels.get(literal) {
+            Some(addr) => Some(*addr),
+            None => return Err(format!("Unknown label \"{}\"", literal)),
+        }
+    };
+    match val {
+        Some(v) if v <= max => Ok(v),
+        Some(v) => Err(format!("Value ${:X} out of range (max ${:X})", v, max)),
+        None => Err(format!("Invalid value \"{}\"", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for i in 0x0000..0xffff {
+            if let Some(opc) = Opcode::new(i) {
+                let bin = assemble(&opc.to_asm()).unwrap();
+                assert_eq!(vec![(i >> 8) as u8, (i & 0xff) as u8], bin);
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_i() {
+        assert_eq!(Ok(vec![0xf0, 0x00, 0xab, 0xcd]), assemble("LONGI $ABCD"));
+        assert_eq!(
+            Ok(vec![0xf0, 0x00, 0x02, 0x06, 0x00, 0xe0, 0x00, 0xee]),
+            assemble("LONGI end\nCLS\nend: RET")
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        let source = "start:\n  MOV V3, #$1F\nloop: ADD V3, #1 ; count\n  JMP loop\n  CALL start\n  SI sprite\nsprite:\n  DB $F0, $90";
+        assert_eq!(
+            Ok(vec![0x63, 0x1f, 0x73, 0x01, 0x12, 0x02, 0x22, 0x00, 0xa2, 0x0a, 0xf0, 0x90]),
+            assemble(source)
+        );
+        assert_eq!(Ok(vec![0x00, 0xe0, 0x12, 0x00]), assemble("été: CLS\nJMP été"));
+    }
+
+    #[test]
+    fn test_data() {
+        assert_eq!(Ok(vec![0x12, 0x34, 0x00, 0x0a]), assemble("DW $1234, 10"));
+        assert_eq!(Ok(vec![0xff, 0x00, 0x05]), assemble("DB #$FF, 0, 5"));
+        assert_eq!(Ok(vec![0x00, 0xe0, 0x02, 0x00]), assemble("a: CLS\nDW a"));
+    }
+
+    #[test]
+    fn test_listing() {
+        let listing = "0200: 00 e0   CLS\n0202: 12 00   JMP $200\n0204: 00 00   [UNDEFINED]";
+        assert_eq!(Ok(vec![0x00, 0xe0, 0x12, 0x00, 0x00, 0x00]), assemble(listing));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(2, assemble("CLS\nFOO V0").unwrap_err().line);
+        assert_eq!(1, assemble("JMP nowhere").unwrap_err().line);
+        assert_eq!(1, assemble("JMP $1000").unwrap_err().line);
+        assert_eq!(1, assemble("MOV VG, #$1").unwrap_err().line);
+        assert_eq!(1, assemble("MOV V1").unwrap_err().line);
+        assert_eq!(2, assemble("a: CLS\na: CLS").unwrap_err().line);
+    }
+}
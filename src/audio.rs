@@ -0,0 +1,162 @@
+use state::Chip8State;
+
+pub const SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / 60;
+const AMPLITUDE: i16 = 8000;
+/// Played while the sound timer runs if the ROM never loaded an XO-CHIP pattern: a square wave
+/// of 4 bits on, 4 bits off, which is 500 Hz at the default pitch.
+const SQUARE_PATTERN: [u8; 16] = [0xf0; 16];
+
+/// What the sound timer and XO-CHIP audio registers were during one instruction.
+#[derive(Clone, Copy)]
+struct Slot {
+    on: bool,
+    pitch: u8,
+    pattern: [u8; 16],
+}
+
+/// Renders the buzzer of a headless run as 16-bit mono PCM.
+///
+/// Like `Tracer`, `before_instruction` is called ahead of every instruction and `end_frame`
+/// after every 60 Hz frame, which renders 1/60 s of samples spread evenly over its instructions.
+pub struct WavRecorder {
+    samples: Vec<i16>,
+    slots: Vec<Slot>,
+    /// Position in the 128 bit pattern, carried across frames so the wave doesn't click.
+    phase: f64,
+}
+
+impl WavRecorder {
+    pub fn new() -> WavRecorder {
+        WavRecorder { samples: vec![], slots: vec![], phase: 0.0 }
+    }
+
+    pub fn before_instruction(&mut self, state: &Chip8State) {
+        let pattern = if state.audio_pattern == [0; 16] { SQUARE_PATTERN } else { state.audio_pattern };
+        self.slots.push(Slot { on: state.sound > 0, pitch: state.pitch, pattern });
+    }
+
+    pub fn end_frame(&mut self) {
+        if self.slots.is_empty() {
+            self.samples.extend_from_slice(&[0; SAMPLES_PER_FRAME]);
+            return;
+        }
+        for n in 0..SAMPLES_PER_FRAME {
+            let slot = self.slots[n * self.slots.len() / SAMPLES_PER_FRAME];
+            if !slot.on {
+                self.samples.push(0);
+                continue;
+            }
+            let bit = self.phase as usize % 128;
+            let high = slot.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            self.samples.push(if high { AMPLITUDE } else { -AMPLITUDE });
+            self.phase = (self.phase + playback_rate(slot.pitch) / SAMPLE_RATE as f64) % 128.0;
+        }
+        self.slots.clear();
+    }
+
+    /// Seconds of sound rendered so far.
+    pub fn duration(&self) -> f64 {
+        self.samples.len() as f64 / SAMPLE_RATE as f64
+    }
+
+    /// Encodes everything rendered so far as a WAV file.
+    pub fn to_wav(&self) -> Vec<u8> {
+        let data_len = self.samples.len() as u32 * 2;
+        let mut out = Vec::with_capacity(44 + data_len as usize);
+        out.extend_from_slice(b"RIFF");
+        push_u32(&mut out, 36 + data_len);
+        out.extend_from_slice(b"WAVEfmt ");
+        push_u32(&mut out, 16);
+        // PCM, mono
+        push_u16(&mut out, 1);
+        push_u16(&mut out, 1);
+        push_u32(&mut out, SAMPLE_RATE);
+        // Byte rate and block align for 16 bit samples
+        push_u32(&mut out, SAMPLE_RATE * 2);
+        push_u16(&mut out, 2);
+        push_u16(&mut out, 16);
+        out.extend_from_slice(b"data");
+        push_u32(&mut out, data_len);
+        for &sample in self.samples.iter() {
+            push_u16(&mut out, sample as u16);
+        }
+        out
+    }
+}
+
+impl Default for WavRecorder {
+    fn default() -> WavRecorder {
+        WavRecorder::new()
+    }
+}
+
+/// Pattern bits played per second, 4000 at the default pitch of 64 and doubling every 48 steps.
+fn playback_rate(pitch: u8) -> f64 {
+    4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0)
+}
+
+fn push_u16(out: &mut Vec<u8>, n: u16) {
+    out.extend_from_slice(&[n as u8, (n >> 8) as u8]);
+}
+
+fn push_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&[n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(recorder: &mut WavRecorder, state: &Chip8State, instructions: usize) {
+        for _ in 0..instructions {
+            recorder.before_instruction(state);
+        }
+        recorder.end_frame();
+    }
+
+    #[test]
+    fn test_square_wave() {
+        let mut state = Chip8State::new();
+        let mut recorder = WavRecorder::new();
+        frame(&mut recorder, &state, 10);
+        state.sound = 1;
+        frame(&mut recorder, &state, 10);
+        assert_eq!(2 * SAMPLES_PER_FRAME, recorder.samples.len());
+        assert!(recorder.samples[..SAMPLES_PER_FRAME].iter().all(|&s| s == 0));
+        // 500 Hz: about 44 samples high, then 44 low
+        let tone = &recorder.samples[SAMPLES_PER_FRAME..];
+        assert!(tone[..44].iter().all(|&s| s == AMPLITUDE));
+        assert!(tone[45..88].iter().all(|&s| s == -AMPLITUDE));
+        assert_eq!(2.0 / 60.0, recorder.duration());
+    }
+
+    #[test]
+    fn test_part_of_frame() {
+        let mut state = Chip8State::new();
+        state.pitch = 0;
+        state.audio_pattern = [0xff; 16];
+        let mut recorder = WavRecorder::new();
+        state.sound = 1;
+        frame(&mut recorder, &state, 0);
+        recorder.before_instruction(&state);
+        state.sound = 0;
+        recorder.before_instruction(&state);
+        recorder.end_frame();
+        assert!(recorder.samples[..SAMPLES_PER_FRAME].iter().all(|&s| s == 0));
+        let (on, off) = recorder.samples[SAMPLES_PER_FRAME..].split_at(SAMPLES_PER_FRAME / 2 + 1);
+        assert!(on.iter().all(|&s| s == AMPLITUDE));
+        assert!(off.iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn test_to_wav() {
+        let mut recorder = WavRecorder::new();
+        recorder.end_frame();
+        let wav = recorder.to_wav();
+        assert_eq!(44 + SAMPLES_PER_FRAME * 2, wav.len());
+        assert_eq!(&b"RIFF"[..], &wav[..4]);
+        assert_eq!(&[0x44, 0xac, 0, 0], &wav[24..28]);
+        assert_eq!(&b"data"[..], &wav[36..40]);
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Instant;
+
+/// Source of the 60 Hz ticks that decrement the delay and sound timers.
+pub trait Clock {
+    /// Total number of 60 Hz ticks since the clock was started.
+    fn ticks(&self) -> u64;
+
+    /// Called once for every executed instruction.
+    fn instruction(&mut self) {}
+
+    /// Advances the clock by one tick. Clocks that follow real time ignore this.
+    fn tick_60hz(&mut self) {}
+}
+
+/// Ticks at 60 Hz of elapsed time, unaffected by changes to the system clock.
+pub struct RealClock {
+    start: Instant,
+}
+
+impl RealClock {
+    pub fn new() -> RealClock {
+        RealClock { start: Instant::now() }
+    }
+}
+
+impl Default for RealClock {
+    fn default() -> RealClock {
+        RealClock::new()
+    }
+}
+
+impl Clock for RealClock {
+    fn ticks(&self) -> u64 {
+        let elapsed = self.start.elapsed();
+        elapsed.as_secs() * 60 + elapsed.subsec_nanos() as u64 * 60 / 1_000_000_000
+    }
+}
+
+/// A deterministic clock, advanced by `tick_60hz` calls or by executed instructions.
+pub struct VirtualClock {
+    ticks: u64,
+    instructions: u64,
+    instructions_per_tick: Option<u64>,
+}
+
+impl VirtualClock {
+    /// A clock that only advances through `tick_60hz`.
+    pub fn new() -> VirtualClock {
+        VirtualClock { ticks: 0, instructions: 0, instructions_per_tick: None }
+    }
+
+    /// A clock that already ticked `ticks` times, e.g. to resume from a save state.
+    pub fn starting_at(ticks: u64) -> VirtualClock {
+        VirtualClock { ticks, instructions: 0, instructions_per_tick: None }
+    }
+
+    /// A clock that ticks once every `n` instructions.
+    pub fn per_instructions(n: u64) -> VirtualClock {
+        assert!(n > 0);
+        VirtualClock { ticks: 0, instructions: 0, instructions_per_tick: Some(n) }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> VirtualClock {
+        VirtualClock::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    fn instruction(&mut self) {
+        if let Some(n) = self.instructions_per_tick {
+            self.instructions += 1;
+            if self.instructions % n == 0 {
+                self.ticks += 1;
+            }
+        }
+    }
+
+    fn tick_60hz(&mut self) {
+        self.ticks += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_real_clock() {
+        let clock = RealClock::new();
+        assert_eq!(0, clock.ticks());
+    }
+
+    #[test]
+    fn test_virtual_clock() {
+        let mut clock = VirtualClock::new();
+        assert_eq!(0, clock.ticks());
+        clock.instruction();
+        assert_eq!(0, clock.ticks());
+        clock.tick_60hz();
+        clock.tick_60hz();
+        assert_eq!(2, clock.ticks());
+    }
+
+    #[test]
+    fn test_virtual_clock_per_instructions() {
+        let mut clock = VirtualClock::per_instructions(3);
+        for _ in 0..8 {
+            clock.instruction();
+        }
+        assert_eq!(2, clock.ticks());
+        clock.tick_60hz();
+        assert_eq!(3, clock.ticks());
+    }
+}
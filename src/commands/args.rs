@@ -0,0 +1,181 @@
+use program::Chip8Program;
+use state::Chip8State;
+use quirks::Quirks;
+use scheduler::DEFAULT_IPF;
+use font;
+use trace::Tracer;
+use coverage::Coverage;
+use profiler::Profiler;
+use audio::WavRecorder;
+use instrument::Instrumentation;
+use flow::Flow;
+use std::io;
+use std::io::{Read, BufWriter};
+use std::fs;
+use std::fs::File;
+use clap::ArgMatches;
+
+pub fn program_from_jnput(matches: &ArgMatches) -> Result<Chip8Program, io::Error> {
+    Chip8Program::from(open_input(matches)?)
+}
+
+pub fn source_from_input(matches: &ArgMatches) -> Result<String, io::Error> {
+    let mut source = String::new();
+    open_input(matches)?.read_to_string(&mut source)?;
+    Ok(source)
+}
+
+/// Reads `--ipf`, printing an error and returning `None` if it is invalid.
+pub fn ipf_from_matches(matches: &ArgMatches) -> Option<u32> {
+    match matches.value_of("ipf").map(|v| v.parse::<u32>()) {
+        None => Some(DEFAULT_IPF),
+        Some(Ok(ipf)) if ipf > 0 => Some(ipf),
+        Some(_) => {
+            println!("Instructions per frame must be a positive number");
+            None
+        }
+    }
+}
+
+/// Creates a `Chip8State` configured by `--seed`, `--profile` and `--font`, printing an error
+/// and returning `None` if one of them is invalid.
+pub fn state_from_matches(matches: &ArgMatches) -> Option<Chip8State> {
+    let mut state: Chip8State = Chip8State::new();
+
+    if let Some(seed) = matches.value_of("seed") {
+        match seed.parse::<u64>() {
+            Ok(seed) => state.seed_rng(seed),
+            Err(_) => {
+                println!("Seed {} is not a number", seed);
+                return None;
+            }
+        }
+    }
+    info!("RNG seed: {}", state.rng_seed());
+
+    if let Some(name) = matches.value_of("profile") {
+        match Quirks::from_name(name) {
+            Some(quirks) => state.quirks = quirks,
+            None => {
+                println!("Unknown profile {}", name);
+                return None;
+            }
+        }
+    }
+
+    if let Some(name) = matches.value_of("font") {
+        let font = match font::from_name(name) {
+            Some(font) => Ok(font),
+            None => font::from_file(name),
+        };
+        match font {
+            Ok(font) => state.load_font(&font),
+            Err(e) => {
+                println!("Font {} can't be loaded: {}", name, e);
+                return None;
+            }
+        }
+    }
+
+    Some(state)
+}
+
+/// Opens the `--trace` file and starts the tools asked for by `--coverage`, `--lcov`, `--hotspots`,
+/// `--hotspots-folded` and `--wav`.
+pub fn instrumentation_from_matches(
+    matches: &ArgMatches,
+    state: &Chip8State,
+) -> Result<Instrumentation<BufWriter<File>>, ()> {
+    let mut instruments = Instrumentation::new();
+    if let Some(path) = matches.value_of("trace") {
+        match File::create(path) {
+            Ok(file) => instruments.tracer = Some(Tracer::new(BufWriter::new(file), state)),
+            Err(e) => {
+                println!("File {} can't be written: {}", path, e);
+                return Err(());
+            }
+        }
+    }
+    if matches.is_present("coverage") || matches.is_present("lcov") {
+        instruments.coverage = Some(Coverage::new());
+    }
+    if matches.is_present("hotspots") || matches.is_present("hotspots-folded") {
+        instruments.profiler = Some(Profiler::new());
+    }
+    if matches.is_present("wav") {
+        instruments.wav = Some(WavRecorder::new());
+    }
+    Ok(instruments)
+}
+
+/// Finishes the trace and writes the coverage and `--wav` files. The profile is printed
+/// separately by `write_profile`.
+pub fn finish_instrumentation(
+    matches: &ArgMatches,
+    instruments: &mut Instrumentation<BufWriter<File>>,
+    state: &Chip8State,
+    program: &Chip8Program,
+) {
+    if let Err(e) = instruments.finish(state) {
+        println!("Trace can't be written: {}", e);
+    }
+    if let Some(ref coverage) = instruments.coverage {
+        write_coverage(matches, coverage, program);
+    }
+    if let (Some(path), Some(wav)) = (matches.value_of("wav"), instruments.wav.as_ref()) {
+        if let Err(e) = fs::write(path, wav.to_wav()) {
+            println!("File {} can't be written: {}", path, e);
+        }
+    }
+}
+
+/// Writes the `--coverage` listing and the `--lcov` tracefile. The tracefile refers to the
+/// listing's lines, so it names the listing as its source, or the ROM without one.
+pub fn write_coverage(matches: &ArgMatches, coverage: &Coverage, program: &Chip8Program) {
+    let flow = Flow::analyze(program);
+    let mut files = vec![];
+    if let Some(path) = matches.value_of("coverage") {
+        files.push((path, coverage.report(&flow).join("\n") + "\n"));
+    }
+    if let Some(path) = matches.value_of("lcov") {
+        let source = matches.value_of("coverage").unwrap_or(matches.value_of("input").unwrap());
+        files.push((path, coverage.lcov(&flow, source)));
+    }
+    for (path, contents) in files {
+        if let Err(e) = fs::write(path, contents) {
+            println!("File {} can't be written: {}", path, e);
+        }
+    }
+}
+
+/// Prints the `--hotspots` report and writes the `--hotspots-folded` stacks.
+pub fn write_profile(matches: &ArgMatches, profiler: &Profiler, state: &Chip8State) {
+    if matches.is_present("hotspots") {
+        for line in profiler.report(state) {
+            println!("{}", line);
+        }
+    }
+    if let Some(path) = matches.value_of("hotspots-folded") {
+        if let Err(e) = fs::write(path, profiler.folded()) {
+            println!("File {} can't be written: {}", path, e);
+        }
+    }
+}
+
+fn open_input(matches: &ArgMatches) -> Result<Box<io::Read>, io::Error> {
+    let input_val = matches.value_of("input").unwrap();
+    if input_val == "-" {
+        Ok(Box::new(io::stdin()))
+    } else {
+        let file = fs::File::open(input_val);
+        if let Err(e) = file {
+            println!(
+                "File {} can't be opened: {}",
+                matches.value_of("input").unwrap(),
+                e
+            );
+            return Err(e);
+        }
+        Ok(Box::new(file.unwrap()))
+    }
+}
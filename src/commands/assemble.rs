@@ -0,0 +1,38 @@
+use clap::ArgMatches;
+use commands::args::*;
+use assembler::assemble;
+use std::fs;
+use std::io;
+use std::io::Write;
+
+pub fn cmd_assemble(matches: &ArgMatches) {
+    let source = source_from_input(matches);
+
+    if let Err(e) = source {
+        println!(
+            "File {} can't be read: {}",
+            matches.value_of("input").unwrap(),
+            e
+        );
+        return;
+    }
+
+    let rom = match assemble(&source.unwrap()) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("{}: {}", matches.value_of("input").unwrap(), e);
+            return;
+        }
+    };
+
+    let output = matches.value_of("output").unwrap();
+    let result = if output == "-" {
+        io::stdout().write_all(&rom)
+    } else {
+        fs::File::create(output).and_then(|mut f| f.write_all(&rom))
+    };
+
+    if let Err(e) = result {
+        println!("File {} can't be written: {}", output, e);
+    }
+}
@@ -1,6 +1,8 @@
 use clap::ArgMatches;
-use util::*;
+use commands::args::*;
 use opcode::Opcode;
+use flow::Flow;
+use state::MAX_PROGRAM_SIZE;
 
 pub fn cmd_decompile(matches: &ArgMatches) {
     let program = program_from_jnput(matches);
@@ -15,18 +17,72 @@ pub fn cmd_decompile(matches: &ArgMatches) {
     }
 
     let program = program.unwrap();
+    if program.instructions.len() * 2 > MAX_PROGRAM_SIZE {
+        println!("Program doesn't fit in memory, the limit is {} bytes", MAX_PROGRAM_SIZE);
+        return;
+    }
+
+    if matches.is_present("flow") {
+        for line in Flow::analyze(&program).listing() {
+            println!("{}", line);
+        }
+        return;
+    }
 
     let mut addr: u16 = 0x200;
+    let instructions = program.instructions;
+    let mut i = 0;
 
-    for instruction in program.instructions {
+    while i < instructions.len() {
+        let instruction = instructions[i];
         let (l, r) = filled_hex_dual(instruction);
-        let asm = Opcode::new(instruction);
+        let mut hex = format!("{} {}", l, r);
+        let asm = Opcode::decode(instruction, instructions.get(i + 1).cloned());
+        let size = asm.as_ref().map_or(2, |oc| oc.size());
         let asm = if let Some(oc) = asm {
+            if let Opcode::LONGI(n) = oc {
+                // The operand is the following word
+                let (l, r) = filled_hex_dual(n);
+                hex = format!("{} {} {}", hex, l, r);
+            }
             oc.to_asm()
         } else {
             "[UNDEFINED]".to_string()
         };
-        println!("{}: {} {}   {}", filled_hex(addr), l, r, asm);
-        addr += 2;
+        println!("{:04x}: {}   {}", addr, hex, asm);
+        addr += size;
+        i += size as usize / 2;
+    }
+}
+
+fn filled_hex_dual(n: u16) -> (String, String) {
+    (
+        format!("{:x}{:x}", (n & 0xf000) >> 12, (n & 0x0f00) >> 8),
+        format!("{:x}{:x}", (n & 0x00f0) >> 4, (n & 0x000f)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filled_hex_dual() {
+        assert_eq!(
+            ("00".to_string(), "00".to_string()),
+            filled_hex_dual(0x0000)
+        );
+        assert_eq!(
+            ("12".to_string(), "34".to_string()),
+            filled_hex_dual(0x1234)
+        );
+        assert_eq!(
+            ("0a".to_string(), "b0".to_string()),
+            filled_hex_dual(0x0ab0)
+        );
+        assert_eq!(
+            ("ff".to_string(), "ff".to_string()),
+            filled_hex_dual(0xffff)
+        );
     }
 }
@@ -1,7 +1,16 @@
 use clap::ArgMatches;
-use util::*;
+use commands::args::*;
 use display;
+use clock::VirtualClock;
+use scheduler::{Scheduler, FramePacer};
+use rewind::{Rewind, DEFAULT_REWIND_SECONDS};
+use debugger::{Debugger, Watchpoint, Condition, parse_addr};
+use fault::Chip8Fault;
+use gdb::{GdbStub, GdbStatus};
 use state::Chip8State;
+use std::fs;
+use std::process;
+use std::net::{TcpListener, TcpStream};
 
 
 pub fn cmd_exec(matches: &ArgMatches) {
@@ -13,23 +22,235 @@ pub fn cmd_exec(matches: &ArgMatches) {
             matches.value_of("input").unwrap(),
             e
         );
-        return;
+        process::exit(1);
     }
 
     let program = program.unwrap();
 
-    let mut state: Chip8State = Chip8State::new();
+    let ipf = match ipf_from_matches(matches) {
+        Some(ipf) => ipf,
+        None => process::exit(1),
+    };
 
-    state.load_program(&program);
+    let rewind_seconds = match matches.value_of("rewind").map(|v| v.parse::<u64>()) {
+        None => DEFAULT_REWIND_SECONDS,
+        Some(Ok(seconds)) => seconds,
+        Some(Err(_)) => {
+            println!("Rewind length must be a number of seconds");
+            process::exit(1);
+        }
+    };
+
+    let mut debugger = match debugger_from_matches(matches) {
+        Ok(debugger) => debugger,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut state = match state_from_matches(matches) {
+        Some(state) => state,
+        None => process::exit(1),
+    };
+
+    if let Err(e) = state.load_program(&program) {
+        println!("{}", e);
+        process::exit(1);
+    }
+    state.set_clock(Box::new(VirtualClock::new()));
+
+    // F5 and F9 save to and load from the --load-state file, or INPUT.state by default
+    let state_path = match matches.value_of("load-state") {
+        Some(path) => path.to_string(),
+        None => format!("{}.state", matches.value_of("input").unwrap()),
+    };
+    if matches.is_present("load-state") {
+        let loaded = fs::read(&state_path)
+            .map_err(|e| e.to_string())
+            .and_then(|buf| state.load_state(&buf).map_err(|e| e.to_string()));
+        if let Err(e) = loaded {
+            println!("State {} can't be loaded: {}", state_path, e);
+            process::exit(1);
+        }
+    }
+
+    // Without --debug, run until the first breakpoint is hit. A gdb client starts paused
+    if let Some(ref mut debugger) = debugger {
+        if !matches.is_present("debug") && !matches.is_present("gdb") {
+            debugger.resume(&state);
+        }
+    }
+
+    let mut instruments = match instrumentation_from_matches(matches, &state) {
+        Ok(instruments) => instruments,
+        Err(()) => process::exit(1),
+    };
+
+    let mut gdb = match matches.value_of("gdb") {
+        Some(port) => match wait_for_gdb(port) {
+            Ok(stub) => Some(stub),
+            Err(e) => {
+                println!("Can't start the gdb server on port {}: {}", port, e);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
 
     println!("Done!");
 
     display::init_display();
 
-    loop {
-        display::update_display(&state);
-        state.exec_step(display::get_char);
+    let mut io = display::TerminalIo::new();
+    let mut scheduler = Scheduler::new(ipf);
+    let mut pacer = FramePacer::new();
+    let mut message = String::new();
+    let mut rewind = Rewind::new((rewind_seconds * 60) as usize);
+
+    while !io.quit && !state.halted {
+        io.poll_input();
+        let mut result: Result<(), Chip8Fault> = Ok(());
+        if let Some(ref mut debugger) = debugger {
+            for command in io.take_commands() {
+                result = result.and_then(|_| match command {
+                    'p' if debugger.paused() => Ok(debugger.resume(&state)),
+                    'p' => Ok(debugger.pause()),
+                    'n' => {
+                        instruments.before_instruction(&state);
+                        debugger.step(&mut state, &mut io)
+                    }
+                    'o' => {
+                        instruments.before_instruction(&state);
+                        debugger.step_over(&mut state, &mut io)
+                    }
+                    'u' => Ok(debugger.step_out(&state)),
+                    'k' => Ok(debugger.toggle_breakpoint(state.pc)),
+                    _ => Ok(()),
+                });
+            }
+        } else {
+            io.take_commands();
+        }
+        let status = match (gdb.as_mut(), debugger.as_mut()) {
+            (Some(stub), Some(debugger)) => stub.poll(&mut state, debugger, &mut io, &mut instruments),
+            _ => Ok(GdbStatus::Attached),
+        };
+        match status {
+            Ok(GdbStatus::Attached) => {}
+            Ok(GdbStatus::Killed) => break,
+            Ok(GdbStatus::Detached) | Err(_) => {
+                gdb = None;
+                if let Some(ref mut debugger) = debugger {
+                    debugger.resume(&state);
+                }
+                message = "gdb detached".to_string();
+            }
+        }
+        if io.rewinding() {
+            instruments.complete(&state);
+            if !rewind.step_back(&mut state) {
+                message = "Start of rewind history".to_string();
+            }
+            display::update_display(&state, &message, debugger.as_ref());
+            pacer.wait();
+            continue;
+        }
+        if !debugger.as_ref().map_or(false, |d| d.paused()) {
+            rewind.record(&state);
+            result = result.and_then(|_| {
+                let cond = |s: &Chip8State| {
+                    if !debugger.as_mut().map_or(true, |d| d.before_instruction(s)) {
+                        return false;
+                    }
+                    instruments.before_instruction(s);
+                    true
+                };
+                if scheduler.run_frame_while(&mut state, &mut io, cond)? {
+                    instruments.end_frame();
+                }
+                Ok(())
+            });
+        }
+        if let Err(fault) = result {
+            // A gdb client gets to inspect the fault instead
+            if let (Some(stub), Some(debugger)) = (gdb.as_mut(), debugger.as_mut()) {
+                if stub.report_fault(&fault, debugger).is_ok() {
+                    message = fault.to_string();
+                    display::update_display(&state, &message, Some(debugger));
+                    pacer.wait();
+                    continue;
+                }
+            }
+            display::close_display();
+            finish_instrumentation(matches, &mut instruments, &state, &program);
+            println!("{}", fault);
+            println!("{:?}", state);
+            if let Some(ref profiler) = instruments.profiler {
+                write_profile(matches, profiler, &state);
+            }
+            process::exit(1);
+        }
+        if io.save_requested {
+            io.save_requested = false;
+            message = match fs::write(&state_path, state.save_state()) {
+                Ok(()) => format!("Saved state to {}", state_path),
+                Err(e) => format!("State {} can't be saved: {}", state_path, e),
+            };
+        }
+        if io.load_requested {
+            io.load_requested = false;
+            instruments.complete(&state);
+            let loaded = fs::read(&state_path)
+                .map_err(|e| e.to_string())
+                .and_then(|buf| state.load_state(&buf).map_err(|e| e.to_string()));
+            message = match loaded {
+                Ok(()) => format!("Loaded state from {}", state_path),
+                Err(e) => format!("State {} can't be loaded: {}", state_path, e),
+            };
+        }
+        display::update_display(&state, &message, debugger.as_ref());
+        pacer.wait();
     }
 
     display::close_display();
+    finish_instrumentation(matches, &mut instruments, &state, &program);
+    if let Some(ref profiler) = instruments.profiler {
+        write_profile(matches, profiler, &state);
+    }
+}
+
+/// Builds the debugger if any of its options are given.
+fn debugger_from_matches(matches: &ArgMatches) -> Result<Option<Debugger>, String> {
+    let options = ["debug", "break", "watch", "break-if", "break-on", "gdb"];
+    if !options.iter().any(|o| matches.is_present(o)) {
+        return Ok(None);
+    }
+    let values = |name| matches.values_of(name).into_iter().flatten();
+    let mut debugger = Debugger::new();
+    for addr in values("break") {
+        let pc = parse_addr(addr).ok_or(format!("Address {} is not a hex number", addr))?;
+        debugger.add_breakpoint(pc);
+    }
+    for range in values("watch") {
+        debugger.add_watchpoint(Watchpoint::parse(range)?);
+    }
+    for condition in values("break-if") {
+        debugger.add_condition(Condition::parse(condition)?);
+    }
+    for mnemonic in values("break-on") {
+        debugger.add_opcode_break(mnemonic);
+    }
+    Ok(Some(debugger))
+}
+
+/// Blocks until a gdb client connects to the local port.
+fn wait_for_gdb(port: &str) -> Result<GdbStub<TcpStream>, String> {
+    let port = port.parse::<u16>().map_err(|_| "not a port number".to_string())?;
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| e.to_string())?;
+    println!("Waiting for gdb on 127.0.0.1:{}", port);
+    let (stream, _) = listener.accept().map_err(|e| e.to_string())?;
+    stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+    stream.set_nodelay(true).map_err(|e| e.to_string())?;
+    Ok(GdbStub::new(stream))
 }
@@ -1,2 +1,6 @@
+pub mod args;
+pub mod assemble;
 pub mod decompile;
-pub mod exec;
\ No newline at end of file
+#[cfg(feature = "ncurses")]
+pub mod exec;
+pub mod run;
@@ -0,0 +1,140 @@
+use clap::ArgMatches;
+use commands::args::*;
+use debugger::parse_addr;
+use clock::VirtualClock;
+use scheduler::Scheduler;
+use io::ScriptedIo;
+use screenshot;
+use std::fs;
+use std::process;
+use std::io::Write;
+
+pub fn cmd_run(matches: &ArgMatches) {
+    let program = program_from_jnput(matches);
+
+    if let Err(e) = program {
+        println!(
+            "File {} can't be read: {}",
+            matches.value_of("input").unwrap(),
+            e
+        );
+        process::exit(1);
+    }
+
+    let program = program.unwrap();
+
+    let ipf = match ipf_from_matches(matches) {
+        Some(ipf) => ipf,
+        None => process::exit(1),
+    };
+
+    let max_instructions = match parse_limit(matches, "instructions") {
+        Ok(n) => n,
+        Err(()) => process::exit(1),
+    };
+    let max_frames = match parse_limit(matches, "frames") {
+        Ok(n) => n,
+        Err(()) => process::exit(1),
+    };
+    let until_pc = match matches.value_of("until-pc").map(|v| (v, parse_addr(v))) {
+        None => None,
+        Some((_, Some(pc))) => Some(pc),
+        Some((v, None)) => {
+            println!("Address {} is not a hex number", v);
+            process::exit(1);
+        }
+    };
+
+    let mut io = match ScriptedIo::parse(matches.value_of("keys").unwrap_or("")) {
+        Ok(io) => io,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut state = match state_from_matches(matches) {
+        Some(state) => state,
+        None => process::exit(1),
+    };
+    if let Err(e) = state.load_program(&program) {
+        println!("{}", e);
+        process::exit(1);
+    }
+    state.set_clock(Box::new(VirtualClock::new()));
+
+    let mut instruments = match instrumentation_from_matches(matches, &state) {
+        Ok(instruments) => instruments,
+        Err(()) => process::exit(1),
+    };
+
+    let mut scheduler = Scheduler::new(ipf);
+    let mut executed: u64 = 0;
+    let mut faulted = false;
+
+    while !state.halted && max_frames.map_or(true, |max| scheduler.frames < max) {
+        io.frame = scheduler.frames;
+        let frame_start = executed;
+        let result = scheduler.run_frame_while(&mut state, &mut io, |s| {
+            if max_instructions.map_or(false, |max| executed >= max) || until_pc == Some(s.pc) {
+                return false;
+            }
+            executed += 1;
+            instruments.before_instruction(s);
+            true
+        });
+        match result {
+            Ok(true) => instruments.end_frame(),
+            Ok(false) => {
+                // Keep the sound and profile of the partial frame the limit stopped in
+                if executed > frame_start {
+                    instruments.end_frame();
+                }
+                break;
+            }
+            Err(fault) => {
+                println!("{}", fault);
+                faulted = true;
+                break;
+            }
+        }
+    }
+
+    finish_instrumentation(matches, &mut instruments, &state, &program);
+
+    match matches.value_of("screen") {
+        Some(path) => {
+            let image = if path.ends_with(".png") {
+                screenshot::to_png(&state.screen)
+            } else if path.ends_with(".pbm") {
+                screenshot::to_pbm(&state.screen)
+            } else {
+                screenshot::to_ascii(&state.screen).into_bytes()
+            };
+            if let Err(e) = fs::File::create(path).and_then(|mut f| f.write_all(&image)) {
+                println!("File {} can't be written: {}", path, e);
+            }
+        }
+        None => print!("{}", screenshot::to_ascii(&state.screen)),
+    }
+    println!("Instructions: {}, frames: {}", executed, scheduler.frames);
+    println!("{:?}", state);
+    if let Some(ref profiler) = instruments.profiler {
+        write_profile(matches, profiler, &state);
+    }
+    // A crashing ROM fails CI runs against golden files
+    if faulted {
+        process::exit(1);
+    }
+}
+
+fn parse_limit(matches: &ArgMatches, name: &str) -> Result<Option<u64>, ()> {
+    match matches.value_of(name).map(|v| v.parse::<u64>()) {
+        None => Ok(None),
+        Some(Ok(n)) => Ok(Some(n)),
+        Some(Err(_)) => {
+            println!("--{} must be a number", name);
+            Err(())
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use state::Chip8State;
+use opcode::Opcode;
+use flow::Flow;
+
+/// The instruction that ran last, whose accesses and skip outcome are counted when the next
+/// one starts.
+struct Last {
+    pc: u16,
+    size: u16,
+    skip: bool,
+}
+
+/// Counts how often each address was executed, read and written while a ROM runs.
+///
+/// Like `Tracer`, `before_instruction` is called ahead of every instruction and `complete`
+/// after the last one.
+pub struct Coverage {
+    executed: Vec<u64>,
+    read: Vec<u64>,
+    written: Vec<u64>,
+    /// How often the skip instruction at an address skipped.
+    skipped: Vec<u64>,
+    last: Option<Last>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        let size = 0x10000;
+        Coverage {
+            executed: vec![0; size],
+            read: vec![0; size],
+            written: vec![0; size],
+            skipped: vec![0; size],
+            last: None,
+        }
+    }
+
+    pub fn before_instruction(&mut self, state: &Chip8State) {
+        self.complete(state);
+        let opcode = state.get_next_opcode();
+        self.executed[state.pc as usize] += 1;
+        self.last = Some(Last {
+            pc: state.pc,
+            size: opcode.as_ref().map_or(2, |oc| oc.size()),
+            skip: opcode.as_ref().map_or(false, is_skip),
+        });
+    }
+
+    /// Counts the accesses of the last instruction now, for when the state is about to change
+    /// outside of instructions.
+    pub fn complete(&mut self, state: &Chip8State) {
+        let last = match self.last.take() {
+            Some(last) => last,
+            None => return,
+        };
+        // The return addresses CALL and RET keep aren't the ROM's data
+        for access in state.last_accesses().iter().filter(|a| !a.stack) {
+            let counts = if access.write { &mut self.written } else { &mut self.read };
+            counts[access.addr as usize] += 1;
+        }
+        if last.skip && state.pc != last.pc.wrapping_add(last.size) {
+            self.skipped[last.pc as usize] += 1;
+        }
+    }
+
+    /// The `decompile --flow` listing with execution counts, or `#####` for code that never ran.
+    /// `R` and `W` mark lines that were read or written as data.
+    pub fn report(&self, flow: &Flow) -> Vec<String> {
+        let mut lines = vec![];
+        let (mut code_lines, mut hit_lines) = (0, 0);
+        for line in flow.listing_lines() {
+            if line.len == 0 {
+                lines.push(format!("{:>16}{}", "", line.text));
+                continue;
+            }
+            let range = line.addr as usize..line.addr as usize + line.len;
+            let executed: u64 = self.executed[range.clone()].iter().sum();
+            let count = if executed > 0 {
+                executed.to_string()
+            } else if line.code {
+                "#####".to_string()
+            } else {
+                "-".to_string()
+            };
+            let read = if self.read[range.clone()].iter().any(|&n| n > 0) { "R" } else { " " };
+            let written = if self.written[range].iter().any(|&n| n > 0) { "W" } else { " " };
+            let mut text = format!("{:>10} {}{}   {}", count, read, written, line.text);
+            if line.code {
+                code_lines += 1;
+                if executed > 0 {
+                    hit_lines += 1;
+                }
+                if is_skip_at(flow, line.addr) {
+                    text.push_str(&format!("   ; skipped {} of {}", self.skipped[line.addr as usize], executed));
+                }
+            }
+            lines.push(text);
+        }
+        let percent = if code_lines > 0 { hit_lines * 100 / code_lines } else { 0 };
+        lines.push(format!("Lines executed: {} of {} ({}%)", hit_lines, code_lines, percent));
+        lines
+    }
+
+    /// An LCOV tracefile for `source`, whose lines are those of `report` without the summary.
+    /// Skips are branches with "not skipped" and "skipped" as their two outcomes.
+    pub fn lcov(&self, flow: &Flow, source: &str) -> String {
+        let mut out = format!("TN:\nSF:{}\n", source);
+        let (mut found, mut hit, mut branches, mut branches_hit) = (0, 0, 0, 0);
+        for (n, line) in flow.listing_lines().iter().enumerate().filter(|&(_, l)| l.code) {
+            let executed = self.executed[line.addr as usize];
+            out.push_str(&format!("DA:{},{}\n", n + 1, executed));
+            found += 1;
+            if executed > 0 {
+                hit += 1;
+            }
+            if is_skip_at(flow, line.addr) {
+                let skipped = self.skipped[line.addr as usize];
+                for (branch, taken) in [executed - skipped, skipped].iter().enumerate() {
+                    let taken = if executed > 0 { taken.to_string() } else { "-".to_string() };
+                    out.push_str(&format!("BRDA:{},0,{},{}\n", n + 1, branch, taken));
+                }
+                branches += 2;
+                branches_hit += (executed > skipped) as u32 + (skipped > 0) as u32;
+            }
+        }
+        out.push_str(&format!("BRF:{}\nBRH:{}\nLF:{}\nLH:{}\nend_of_record\n", branches, branches_hit, found, hit));
+        out
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Coverage {
+        Coverage::new()
+    }
+}
+
+fn is_skip(opcode: &Opcode) -> bool {
+    match *opcode {
+        Opcode::SKIPEQ(_, _) | Opcode::SKIPNEQ(_, _) | Opcode::SKIPREQ(_, _) |
+        Opcode::SKIPRNEQ(_, _) | Opcode::SKIPKEQ(_) | Opcode::SKIPKNEQ(_) => true,
+        _ => false,
+    }
+}
+
+fn is_skip_at(flow: &Flow, addr: u16) -> bool {
+    flow.opcode_at(addr).as_ref().map_or(false, is_skip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use io::NullIo;
+    use program::Chip8Program;
+
+    fn run() -> (Coverage, Flow) {
+        // 200: SKIPEQ V0, #$2, 202: JMP $206, 204: JMP $20A, 206: ADD V0, #$1, 208: JMP $200,
+        // 20A: BCD V0, 20C: SKIPNEQ V0, #$2, 20E: JMP $20C, 210: CLS
+        let program = Chip8Program::new(&[
+            0x30, 0x02, 0x12, 0x06, 0x12, 0x0a, 0x70, 0x01, 0x12, 0x00, 0xf0, 0x33, 0x40, 0x02, 0x12, 0x0c,
+            0x00, 0xe0,
+        ]);
+        let mut state = Chip8State::new();
+        state.i = 0x210;
+        state.load_program(&program).unwrap();
+        let mut coverage = Coverage::new();
+        for _ in 0..12 {
+            coverage.before_instruction(&state);
+            state.exec_step(&mut NullIo).unwrap();
+        }
+        coverage.complete(&state);
+        (coverage, Flow::analyze(&program))
+    }
+
+    #[test]
+    fn test_report() {
+        let (coverage, flow) = run();
+        assert_eq!(
+            vec![
+                "                L200:",
+                "         3      0200: 30 02   SKIPEQ V0, #$2   ; skipped 1 of 3",
+                "         2      0202: 12 06   JMP L206",
+                "         1      0204: 12 0a   JMP L20A",
+                "                L206:",
+                "         2      0206: 70 01   ADD V0, #$1",
+                "         2      0208: 12 00   JMP L200",
+                "                L20A:",
+                "         1      020a: f0 33   BCD V0",
+                "                L20C:",
+                "         1      020c: 40 02   SKIPNEQ V0, #$2   ; skipped 0 of 1",
+                "     #####      020e: 12 0c   JMP L20C",
+                "     #####  W   0210: 00 e0   CLS",
+                "Lines executed: 7 of 9 (77%)",
+            ],
+            coverage.report(&flow)
+        );
+    }
+
+    #[test]
+    fn test_lcov() {
+        let (coverage, flow) = run();
+        let lcov = coverage.lcov(&flow, "game.lst");
+        assert!(lcov.starts_with("TN:\nSF:game.lst\nDA:2,3\nBRDA:2,0,0,2\nBRDA:2,0,1,1\nDA:3,2\n"));
+        assert!(lcov.contains("DA:11,1\nBRDA:11,0,0,1\nBRDA:11,0,1,0\nDA:12,0\nDA:13,0\n"));
+        assert!(lcov.ends_with("BRF:4\nBRH:3\nLF:9\nLH:7\nend_of_record\n"));
+    }
+
+    #[test]
+    fn test_stack_is_not_data() {
+        let mut state = Chip8State::new();
+        // 200: CALL $204, 202: JMP $202, 204: RET
+        state.load_program(&Chip8Program::new(&[0x22, 0x04, 0x12, 0x02, 0x00, 0xee])).unwrap();
+        let mut coverage = Coverage::new();
+        for _ in 0..3 {
+            coverage.before_instruction(&state);
+            state.exec_step(&mut NullIo).unwrap();
+        }
+        coverage.complete(&state);
+        assert_eq!(0x202, state.pc);
+        assert!(coverage.read.iter().chain(coverage.written.iter()).all(|&n| n == 0));
+    }
+}
@@ -0,0 +1,592 @@
+use state::{Chip8State, MemAccess};
+use opcode::Opcode;
+use io::Chip8Io;
+use fault::Chip8Fault;
+use std::collections::BTreeSet;
+use std::fmt;
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+enum Mode {
+    Running,
+    Paused,
+    /// Run until the stack is back to this depth, i.e. the stepped-over `CALL` returned.
+    StepOver(u8),
+    /// Run until the stack is shallower than this, i.e. the current subroutine returned.
+    StepOut(u8),
+}
+
+/// Which accesses a watchpoint stops on.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Watch {
+    Read,
+    Write,
+    Access,
+}
+
+/// Stops after an instruction reads or writes memory in `start..=end`.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub watch: Watch,
+}
+
+impl Watchpoint {
+    /// Parses a hex `ADDR` or `START-END`, optionally followed by `:r` or `:w` to only stop on
+    /// reads or writes.
+    pub fn parse(s: &str) -> Result<Watchpoint, String> {
+        let (range, watch) = match s.rfind(':') {
+            Some(n) => match &s[n + 1..] {
+                "r" => (&s[..n], Watch::Read),
+                "w" => (&s[..n], Watch::Write),
+                "rw" => (&s[..n], Watch::Access),
+                other => return Err(format!("Unknown watchpoint access {}, expected r, w or rw", other)),
+            },
+            None => (s, Watch::Access),
+        };
+        let addr = |a: &str| parse_addr(a).ok_or(format!("Address {} is not a hex number", a));
+        let (start, end) = match range.find('-') {
+            Some(n) => (addr(&range[..n])?, addr(&range[n + 1..])?),
+            None => (addr(range)?, addr(range)?),
+        };
+        if end < start {
+            return Err(format!("Watchpoint {} ends before it starts", s));
+        }
+        Ok(Watchpoint { start, end, watch })
+    }
+
+    fn hit(&self, access: &MemAccess) -> bool {
+        let kind = match self.watch {
+            Watch::Read => !access.write,
+            Watch::Write => access.write,
+            Watch::Access => true,
+        };
+        kind && access.addr >= self.start && access.addr <= self.end
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+enum Operand {
+    V(usize),
+    I,
+    PC,
+    SP,
+    DT,
+    ST,
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+enum Cmp {
+    Eq,
+    Ne,
+    Le,
+    Ge,
+    Lt,
+    Gt,
+}
+
+// Two character operators first, so `<=` isn't taken for `<`
+const CMPS: [(&str, Cmp); 6] = [
+    ("==", Cmp::Eq),
+    ("!=", Cmp::Ne),
+    ("<=", Cmp::Le),
+    (">=", Cmp::Ge),
+    ("<", Cmp::Lt),
+    (">", Cmp::Gt),
+];
+
+/// A comparison of a register with a constant, such as `V3 == 0x10` or `I > $E00`.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Condition {
+    operand: Operand,
+    cmp: Cmp,
+    value: u16,
+}
+
+impl Condition {
+    /// Parses `REG OP VALUE`. REG is one of V0-VF, I, PC, SP, DT or ST, OP one of
+    /// `== != < <= > >=`, and VALUE is decimal or hex with a `0x` or `$` prefix.
+    pub fn parse(s: &str) -> Result<Condition, String> {
+        let (n, op, cmp) = CMPS
+            .iter()
+            .filter_map(|&(op, cmp)| s.find(op).map(|n| (n, op, cmp)))
+            .next()
+            .ok_or(format!("Condition {} has no comparison", s))?;
+        let reg = s[..n].trim().to_uppercase();
+        let operand = match reg.as_ref() {
+            "I" => Operand::I,
+            "PC" => Operand::PC,
+            "SP" => Operand::SP,
+            "DT" => Operand::DT,
+            "ST" => Operand::ST,
+            r if r.len() == 2 && r.starts_with('V') => match u8::from_str_radix(&r[1..], 16) {
+                Ok(v) => Operand::V(v as usize),
+                Err(_) => return Err(format!("Unknown register {}", reg)),
+            },
+            _ => return Err(format!("Unknown register {}", reg)),
+        };
+        let value = s[n + op.len()..].trim();
+        let parsed = if value.starts_with("0x") || value.starts_with('$') {
+            parse_addr(value)
+        } else {
+            value.parse::<u16>().ok()
+        };
+        match parsed {
+            Some(value) => Ok(Condition { operand, cmp, value }),
+            None => Err(format!("Value {} is not a number", value)),
+        }
+    }
+
+    pub fn holds(&self, state: &Chip8State) -> bool {
+        let lhs = match self.operand {
+            Operand::V(v) => state.vregs[v] as u16,
+            Operand::I => state.i,
+            Operand::PC => state.pc,
+            Operand::SP => state.sp as u16,
+            Operand::DT => state.delay as u16,
+            Operand::ST => state.sound as u16,
+        };
+        match self.cmp {
+            Cmp::Eq => lhs == self.value,
+            Cmp::Ne => lhs != self.value,
+            Cmp::Le => lhs <= self.value,
+            Cmp::Ge => lhs >= self.value,
+            Cmp::Lt => lhs < self.value,
+            Cmp::Gt => lhs > self.value,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = CMPS.iter().find(|&&(_, cmp)| cmp == self.cmp).unwrap().0;
+        match self.operand {
+            Operand::V(v) => write!(f, "V{:X}", v)?,
+            other => write!(f, "{:?}", other)?,
+        }
+        write!(f, " {} {:#X}", op, self.value)
+    }
+}
+
+/// Decides when execution stops: breakpoints, single steps and stepping over or out of calls.
+///
+/// The frontend asks `before_instruction` ahead of every instruction while it runs frames.
+/// Watchpoints are checked against the accesses of the instruction that just ran, so execution
+/// stops right after the access.
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    /// Conditions with whether they held at the last check, they stop when they become true.
+    conditions: Vec<(Condition, bool)>,
+    opcode_breaks: BTreeSet<String>,
+    stop_reason: Option<String>,
+    mode: Mode,
+    /// Set on resume so the breakpoint under PC doesn't stop execution again straight away.
+    resume_pc: Option<u16>,
+}
+
+impl Debugger {
+    /// A debugger that starts paused at the first instruction.
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            watchpoints: vec![],
+            conditions: vec![],
+            opcode_breaks: BTreeSet::new(),
+            stop_reason: None,
+            mode: Mode::Paused,
+            resume_pc: None,
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    pub fn resume(&mut self, state: &Chip8State) {
+        self.run(state, Mode::Running);
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<u16> {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.remove(&pc);
+    }
+
+    pub fn toggle_breakpoint(&mut self, pc: u16) {
+        if !self.breakpoints.remove(&pc) {
+            self.breakpoints.insert(pc);
+        }
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn add_condition(&mut self, condition: Condition) {
+        self.conditions.push((condition, false));
+    }
+
+    /// Stops before any instruction with this mnemonic, e.g. `DRAW`.
+    pub fn add_opcode_break(&mut self, mnemonic: &str) {
+        self.opcode_breaks.insert(mnemonic.to_uppercase());
+    }
+
+    /// Why execution last stopped on its own, if it did.
+    pub fn stop_reason(&self) -> Option<&str> {
+        self.stop_reason.as_ref().map(|r| r.as_ref())
+    }
+
+    /// Executes exactly one instruction and stays paused.
+    pub fn step<T: Chip8Io>(&mut self, state: &mut Chip8State, io: &mut T) -> Result<(), Chip8Fault> {
+        self.mode = Mode::Paused;
+        state.exec_step(io)
+    }
+
+    /// Like `step`, but runs a `CALL` until it returns.
+    pub fn step_over<T: Chip8Io>(&mut self, state: &mut Chip8State, io: &mut T) -> Result<(), Chip8Fault> {
+        let sp = state.sp;
+        let is_call = match state.get_next_opcode() {
+            Some(Opcode::CALL(_)) => true,
+            _ => false,
+        };
+        self.step(state, io)?;
+        if is_call && state.sp > sp {
+            self.run(state, Mode::StepOver(sp));
+        }
+        Ok(())
+    }
+
+    /// Runs until the current subroutine returns.
+    pub fn step_out(&mut self, state: &Chip8State) {
+        self.run(state, Mode::StepOut(state.sp));
+    }
+
+    fn run(&mut self, state: &Chip8State, mode: Mode) {
+        self.mode = mode;
+        self.stop_reason = None;
+        self.resume_pc = Some(state.pc);
+    }
+
+    /// Returns whether the next instruction may run, pausing if it may not.
+    pub fn before_instruction(&mut self, state: &Chip8State) -> bool {
+        let resumed_here = self.resume_pc.take() == Some(state.pc);
+        let stop = match self.mode {
+            Mode::Paused => true,
+            Mode::Running => false,
+            Mode::StepOver(sp) => state.sp <= sp,
+            Mode::StepOut(sp) => state.sp < sp,
+        };
+        // Conditions are updated even when not stopping, so they only fire when they become true
+        let mut reason = None;
+        for &mut (ref condition, ref mut held) in self.conditions.iter_mut() {
+            let holds = condition.holds(state);
+            if holds && !*held && reason.is_none() {
+                reason = Some(format!("Condition {}", condition));
+            }
+            *held = holds;
+        }
+        if !resumed_here {
+            reason = reason.or_else(|| self.break_reason(state));
+        }
+        if stop || reason.is_some() {
+            self.mode = Mode::Paused;
+            if reason.is_some() {
+                self.stop_reason = reason;
+            }
+            return false;
+        }
+        true
+    }
+
+    fn break_reason(&self, state: &Chip8State) -> Option<String> {
+        if self.breakpoints.contains(&state.pc) {
+            return Some(format!("Breakpoint at {:04X}", state.pc));
+        }
+        for access in state.last_accesses() {
+            if self.watchpoints.iter().any(|w| w.hit(access)) {
+                let (verb, prep) = if access.write { ("Write", "to") } else { ("Read", "from") };
+                return Some(format!("{} {:02X} {} {:04X}", verb, access.value, prep, access.addr));
+            }
+        }
+        if !self.opcode_breaks.is_empty() {
+            if let Some(opcode) = state.get_next_opcode() {
+                if self.opcode_breaks.contains(opcode.to_asm_code()) {
+                    return Some(format!("{} at {:04X}", opcode.to_asm_code(), state.pc));
+                }
+            }
+        }
+        None
+    }
+
+    /// Disassembles `count` instructions starting `count / 2` words before PC, marking PC with
+    /// `>` and breakpoints with `*`.
+    pub fn disassembly(&self, state: &Chip8State, count: usize) -> Vec<String> {
+        let mut lines = vec![];
+        // Instructions are usually aligned with PC, so start a few words back
+        let mut addr = state.pc.saturating_sub(2 * (count as u16 / 2));
+        while lines.len() < count && (addr as usize) + 1 < state.mem.len() {
+            let word = word_at(state, addr);
+            let next = if (addr as usize) + 3 < state.mem.len() {
+                Some(word_at(state, addr + 2))
+            } else {
+                None
+            };
+            let opcode = Opcode::decode(word, next);
+            let asm = opcode.as_ref().map_or("[UNDEFINED]".to_string(), |oc| oc.to_asm());
+            lines.push(format!(
+                "{}{}{:04X}: {:04X}  {}",
+                if addr == state.pc { ">" } else { " " },
+                if self.breakpoints.contains(&addr) { "*" } else { " " },
+                addr,
+                word,
+                asm
+            ));
+            addr = addr.wrapping_add(opcode.map_or(2, |oc| oc.size()));
+            if addr == 0 {
+                break;
+            }
+        }
+        lines
+    }
+
+    /// Hex dump of `rows` lines of 8 bytes starting at I.
+    pub fn memory_view(&self, state: &Chip8State, rows: usize) -> Vec<String> {
+        (0..rows)
+            .map(|row| state.i as usize + row * 8)
+            .filter(|&addr| addr < state.mem.len())
+            .map(|addr| {
+                let end = (addr + 8).min(state.mem.len());
+                let bytes: Vec<String> =
+                    state.mem[addr..end].iter().map(|b| format!("{:02X}", b)).collect();
+                format!("{:04X}: {}", addr, bytes.join(" "))
+            })
+            .collect()
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+/// Parses a hex address written as `2A4`, `0x2A4` or `$2A4`.
+pub fn parse_addr(s: &str) -> Option<u16> {
+    let hex = if s.starts_with("0x") || s.starts_with("0X") {
+        &s[2..]
+    } else if s.starts_with('$') {
+        &s[1..]
+    } else {
+        s
+    };
+    u16::from_str_radix(hex, 16).ok()
+}
+
+fn word_at(state: &Chip8State, addr: u16) -> u16 {
+    ((state.mem[addr as usize] as u16) << 8) | state.mem[addr as usize + 1] as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use io::NullIo;
+    use program::Chip8Program;
+    use quirks::LoadStoreI;
+
+    /// Runs until the debugger stops execution, like the frontend does frame by frame.
+    fn run(debugger: &mut Debugger, state: &mut Chip8State) {
+        for _ in 0..1000 {
+            if !debugger.before_instruction(state) {
+                return;
+            }
+            state.exec_step(&mut NullIo).unwrap();
+        }
+        panic!("Debugger never stopped");
+    }
+
+    fn program() -> Chip8State {
+        let mut state = Chip8State::new();
+        // 200: CALL $206, 202: ADD V1, #$1, 204: JMP $200, 206: ADD V0, #$1, 208: RET
+        state.load_program(&Chip8Program::new(&[
+            0x22, 0x06, 0x71, 0x01, 0x12, 0x00, 0x70, 0x01, 0x00, 0xee,
+        ])).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_parse_addr() {
+        assert_eq!(Some(0x2a4), parse_addr("2A4"));
+        assert_eq!(Some(0x2a4), parse_addr("0x2a4"));
+        assert_eq!(Some(0x2a4), parse_addr("$2A4"));
+        assert_eq!(None, parse_addr("2G4"));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut state = program();
+        let mut debugger = Debugger::new();
+        assert!(!debugger.before_instruction(&state));
+        debugger.add_breakpoint(0x208);
+        debugger.resume(&state);
+        run(&mut debugger, &mut state);
+        assert!(debugger.paused());
+        assert_eq!(0x208, state.pc);
+        // Continuing from a breakpoint doesn't stop on it again at once
+        debugger.resume(&state);
+        run(&mut debugger, &mut state);
+        assert_eq!(0x208, state.pc);
+        assert_eq!(2, state.vregs[0x0]);
+        debugger.toggle_breakpoint(0x208);
+        assert!(debugger.breakpoints().is_empty());
+    }
+
+    #[test]
+    fn test_step() {
+        let mut state = program();
+        let mut debugger = Debugger::new();
+        debugger.step(&mut state, &mut NullIo).unwrap();
+        assert_eq!(0x206, state.pc);
+        assert!(debugger.paused());
+        assert!(!debugger.before_instruction(&state));
+    }
+
+    #[test]
+    fn test_step_over() {
+        let mut state = program();
+        let mut debugger = Debugger::new();
+        debugger.step_over(&mut state, &mut NullIo).unwrap();
+        run(&mut debugger, &mut state);
+        assert_eq!(0x202, state.pc);
+        assert_eq!(0, state.sp);
+        assert_eq!(1, state.vregs[0x0]);
+
+        // Not a CALL, so the same as a step
+        debugger.step_over(&mut state, &mut NullIo).unwrap();
+        assert!(!debugger.before_instruction(&state));
+        assert_eq!(0x204, state.pc);
+    }
+
+    #[test]
+    fn test_step_out() {
+        let mut state = program();
+        let mut debugger = Debugger::new();
+        debugger.step(&mut state, &mut NullIo).unwrap();
+        debugger.step_out(&state);
+        run(&mut debugger, &mut state);
+        assert_eq!(0x202, state.pc);
+        assert_eq!(0, state.sp);
+    }
+
+    #[test]
+    fn test_parse_watchpoint() {
+        assert_eq!(Ok(Watchpoint { start: 0x300, end: 0x300, watch: Watch::Access }), Watchpoint::parse("300"));
+        assert_eq!(Ok(Watchpoint { start: 0x300, end: 0x30f, watch: Watch::Write }), Watchpoint::parse("300-30F:w"));
+        assert_eq!(Ok(Watchpoint { start: 0x300, end: 0x300, watch: Watch::Read }), Watchpoint::parse("$300:r"));
+        assert!(Watchpoint::parse("300:x").is_err());
+        assert!(Watchpoint::parse("30F-300").is_err());
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        let mut state = Chip8State::new();
+        state.vregs[0x3] = 0x10;
+        state.i = 0xe00;
+        assert!(Condition::parse("V3 == 0x10").unwrap().holds(&state));
+        assert!(!Condition::parse("v3!=16").unwrap().holds(&state));
+        assert!(Condition::parse("I >= $E00").unwrap().holds(&state));
+        assert!(!Condition::parse("I > 0xE00").unwrap().holds(&state));
+        assert_eq!("PC <= 0x200", Condition::parse("PC <= 512").unwrap().to_string());
+        assert!(Condition::parse("VG == 1").is_err());
+        assert!(Condition::parse("V3 = 1").is_err());
+        assert!(Condition::parse("V3 == x").is_err());
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut state = Chip8State::new();
+        state.i = 0x300;
+        // 200: MOV V0, #$7, 202: RDUMP V0, 204: RLOAD V0, 206: JMP $200
+        state.load_program(&Chip8Program::new(&[0x60, 0x07, 0xf0, 0x55, 0xf0, 0x65, 0x12, 0x00])).unwrap();
+        state.quirks.load_store_i = LoadStoreI::Unchanged;
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(Watchpoint::parse("300:r").unwrap());
+        debugger.resume(&state);
+        run(&mut debugger, &mut state);
+        // Stops after the read, not the write before it
+        assert_eq!(0x206, state.pc);
+        assert_eq!(Some("Read 07 from 0300"), debugger.stop_reason());
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut state = program();
+        let mut debugger = Debugger::new();
+        debugger.add_condition(Condition::parse("V0 == 2").unwrap());
+        debugger.resume(&state);
+        run(&mut debugger, &mut state);
+        assert_eq!(2, state.vregs[0x0]);
+        assert_eq!(0x208, state.pc);
+        assert_eq!(Some("Condition V0 == 0x2"), debugger.stop_reason());
+        // Only stops again once the condition has been false in between
+        debugger.resume(&state);
+        for _ in 0..20 {
+            assert!(debugger.before_instruction(&state));
+            state.exec_step(&mut NullIo).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_opcode_breaks() {
+        let mut state = program();
+        let mut debugger = Debugger::new();
+        debugger.add_opcode_break("ret");
+        debugger.resume(&state);
+        run(&mut debugger, &mut state);
+        assert_eq!(0x208, state.pc);
+        assert_eq!(Some("RET at 0208"), debugger.stop_reason());
+    }
+
+    #[test]
+    fn test_views() {
+        let mut state = program();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+        debugger.step(&mut state, &mut NullIo).unwrap();
+        assert_eq!(
+            vec![
+                " *0202: 7101  ADD V1, #$1",
+                "  0204: 1200  JMP $200",
+                "> 0206: 7001  ADD V0, #$1",
+                "  0208: 00EE  RET",
+            ],
+            debugger.disassembly(&state, 4)
+        );
+        assert_eq!(vec![0x200], state.call_stack());
+        state.i = 0x200;
+        assert_eq!(
+            vec!["0200: 22 06 71 01 12 00 70 01", "0208: 00 EE 00 00 00 00 00 00"],
+            debugger.memory_view(&state, 2)
+        );
+    }
+}
@@ -1,68 +1,231 @@
 use ncurses::*;
 use state::Chip8State;
+use io::Chip8Io;
+use debugger::Debugger;
 use std::char::from_u32;
+use std::time::{Duration, Instant};
 
-const WIDTH: u8 = 64;
-const HEIGHT: u8 = 32;
+/// Characters for the four XO-CHIP colours, indexed by the planes a pixel is set in.
+const PLANE_CHARS: [&str; 4] = [" ", "0", "+", "#"];
 
 pub fn init_display() {
     initscr();
+    noecho();
+    keypad(stdscr(), true);
+    timeout(0);
     attr_on(A_REVERSE());
     printw("Chip8");
     attr_off(A_REVERSE());
     printw("Chip8");
 }
 
-pub fn update_display(state: &Chip8State) {
+/// Redraws the screen and the status lines, with `message` on the last line.
+///
+/// With a debugger the disassembly, memory at I, call stack and breakpoints go right of the screen.
+pub fn update_display(state: &Chip8State, message: &str, debugger: Option<&Debugger>) {
     clear();
     let mut on_pxs = 0;
-    for y in 0..HEIGHT {
-        for x in 0..WIDTH {
-            let on = state.pixel_on(x, y);
+    let (width, height) = (state.screen.width(), state.screen.height());
+    for y in 0..height {
+        for x in 0..width {
+            let planes = state.screen.planes(x, y);
+            let on = planes != 0;
             if on {
                 on_pxs = on_pxs + 1;
             }
             let attr_fn = if on { attr_on } else { attr_off };
             attr_fn(A_REVERSE());
-            mvprintw(y as i32, x as i32, if on {"0"} else {" "});
+            mvprintw(y as i32, x as i32, PLANE_CHARS[planes as usize]);
         }
     }
 
     attr_off(A_REVERSE());
 
-    let opcode = state.get_next_opcode().unwrap();
-    mvprintw(32, 0, format!("PC: 0x{:X} (0x{:X} / {})", state.pc, opcode.to_bin(), opcode.to_asm()).as_ref());
-    mvprintw(33, 0, format!("I: 0x{:X}, On pixels: {}", state.i, on_pxs).as_ref());
+    let row = height as i32;
+    mvprintw(row, 0, pc_line(state).as_ref());
+    mvprintw(row + 1, 0, format!("I: 0x{:X}, On pixels: {}", state.i, on_pxs).as_ref());
     for i in 0..4 {
         let r = i * 4;
-        mvprintw(34 + i, 0, format!("V{:X}: 0x{:X}, V{:X}: 0x{:X}, V{:X}: 0x{:X}, V{:X}: 0x{:X}",
+        mvprintw(row + 2 + i, 0, format!("V{:X}: 0x{:X}, V{:X}: 0x{:X}, V{:X}: 0x{:X}, V{:X}: 0x{:X}",
                                     r, state.vregs[r as usize],
                                     r + 1, state.vregs[(r + 1) as usize],
                                     r + 2, state.vregs[(r + 2) as usize],
                                     r + 3, state.vregs[(r + 3) as usize]).as_ref());
     }
-    mvprintw(38, 0, format!("DELAY: {}, SOUND: {}, UPDATES: {}", state.delay, state.sound, state.timer_updates).as_ref());
+    mvprintw(row + 6, 0, format!("DELAY: {}, SOUND: {}, UPDATES: {}, SEED: {}", state.delay, state.sound, state.timer_updates, state.rng_seed()).as_ref());
+    if state.sound > 0 {
+        attr_on(A_REVERSE());
+        printw(" BEEP ");
+        attr_off(A_REVERSE());
+    }
+    mvprintw(row + 7, 0, message);
+
+    if let Some(debugger) = debugger {
+        draw_debugger(state, debugger, width as i32 + 2);
+    }
 
     refresh();
 }
 
-pub fn get_char() -> u8 {
-    loop {
-        let key = getch() as u32;
-        refresh();
-        let c = from_u32(key);
-        if c.is_none() { continue; }
-        let c = c.unwrap();
-        if c >= '0' && c <= '9' {
-            return (c as u8) - '0' as u8;
+fn draw_debugger(state: &Chip8State, debugger: &Debugger, col: i32) {
+    let status = if debugger.paused() { "PAUSED" } else { "RUNNING" };
+    mvprintw(0, col, format!("{}  p:continue n:step o:over u:out k:break", status).as_ref());
+    if let Some(reason) = debugger.stop_reason() {
+        mvprintw(1, col, reason);
+    }
+    let mut row = 2;
+    for line in debugger.disassembly(state, 12) {
+        mvprintw(row, col, line.as_ref());
+        row += 1;
+    }
+    row += 1;
+    for line in debugger.memory_view(state, 4) {
+        mvprintw(row, col, line.as_ref());
+        row += 1;
+    }
+    row += 1;
+    let calls: Vec<String> = state.call_stack().iter().map(|pc| format!("{:04X}", pc)).collect();
+    mvprintw(row, col, format!("Calls: {}", calls.join(" ")).as_ref());
+    let breakpoints: Vec<String> = debugger.breakpoints().iter().map(|pc| format!("{:04X}", pc)).collect();
+    mvprintw(row + 1, col, format!("Breakpoints: {}", breakpoints.join(" ")).as_ref());
+}
+
+/// Terminals only report key presses, so a key counts as held until this long after its last repeat.
+const KEY_HOLD_MS: u64 = 150;
+
+/// `Chip8Io` for the ncurses frontend.
+pub struct TerminalIo {
+    held_until: [Option<Instant>; 16],
+    /// Set once the quit key (q or Escape) was pressed.
+    pub quit: bool,
+    /// Set when F5 was pressed, cleared by the caller once the state is saved.
+    pub save_requested: bool,
+    /// Set when F9 was pressed, cleared by the caller once the state is loaded.
+    pub load_requested: bool,
+    rewind_until: Option<Instant>,
+    commands: Vec<char>,
+}
+
+impl TerminalIo {
+    pub fn new() -> TerminalIo {
+        TerminalIo {
+            held_until: [None; 16],
+            quit: false,
+            save_requested: false,
+            load_requested: false,
+            rewind_until: None,
+            commands: vec![],
         }
-        let c = c.to_ascii_lowercase();
-        if c >= 'a' && c <= 'f' {
-            return (c as u8) - 'a' as u8 + 0xa;
+    }
+
+    /// True while the rewind key (r) is held.
+    pub fn rewinding(&self) -> bool {
+        self.rewind_until.map_or(false, |until| until > Instant::now())
+    }
+
+    /// Returns the other keys pressed since the last call, for the debugger.
+    pub fn take_commands(&mut self) -> Vec<char> {
+        self.commands.split_off(0)
+    }
+
+    /// Reads all pending key presses without blocking.
+    pub fn poll_input(&mut self) {
+        let now = Instant::now();
+        let hold = now + Duration::from_millis(KEY_HOLD_MS);
+        loop {
+            let key = getch();
+            if key == ERR {
+                break;
+            }
+            if key == 'q' as i32 || key == 27 {
+                self.quit = true;
+            }
+            if key == KEY_F(5) {
+                self.save_requested = true;
+            }
+            if key == KEY_F(9) {
+                self.load_requested = true;
+            }
+            if key == 'r' as i32 {
+                self.rewind_until = Some(hold);
+            }
+            match from_u32(key as u32) {
+                Some(c) => match hex_key(c) {
+                    Some(k) => self.held_until[k as usize] = Some(hold),
+                    None => self.commands.push(c),
+                },
+                None => {}
+            }
         }
     }
 }
 
+impl Default for TerminalIo {
+    fn default() -> TerminalIo {
+        TerminalIo::new()
+    }
+}
+
+impl Chip8Io for TerminalIo {
+    /// Rings the terminal bell when the sound starts, the status line shows it while it lasts.
+    fn sound(&mut self, on: bool) {
+        if on {
+            beep();
+        }
+    }
+
+    fn update_keys(&mut self, keys: &mut [bool; 16]) {
+        self.poll_input();
+        let now = Instant::now();
+        for k in 0..16 {
+            let held = match self.held_until[k] {
+                Some(until) => until > now,
+                None => false,
+            };
+            keys[k] = held;
+        }
+    }
+}
+
+fn hex_key(c: char) -> Option<u8> {
+    if c >= '0' && c <= '9' {
+        return Some((c as u8) - '0' as u8);
+    }
+    let c = c.to_ascii_lowercase();
+    if c >= 'a' && c <= 'f' {
+        return Some((c as u8) - 'a' as u8 + 0xa);
+    }
+    None
+}
+
+/// PC with the word there and its disassembly, which may not decode after a fault or when a
+/// debugger moved PC.
+fn pc_line(state: &Chip8State) -> String {
+    let asm = state.get_next_opcode().map_or("[UNDEFINED]".to_string(), |oc| oc.to_asm());
+    match state.get_next_word() {
+        Some(word) => format!("PC: 0x{:X} (0x{:X} / {})", state.pc, word, asm),
+        None => format!("PC: 0x{:X} ({})", state.pc, asm),
+    }
+}
+
 pub fn close_display() {
     endwin();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use program::Chip8Program;
+
+    #[test]
+    fn test_pc_line() {
+        let mut state = Chip8State::new();
+        // MOV V0, #$2A, then an undefined word
+        state.load_program(&Chip8Program::new(&[0x60, 0x2a, 0xff, 0xff])).unwrap();
+        assert_eq!("PC: 0x200 (0x602A / MOV V0, #$2A)", pc_line(&state));
+        state.pc = 0x202;
+        assert_eq!("PC: 0x202 (0xFFFF / [UNDEFINED])", pc_line(&state));
+        state.pc = 0xffff;
+        assert_eq!("PC: 0xFFFF ([UNDEFINED])", pc_line(&state));
+    }
+}
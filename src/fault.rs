@@ -0,0 +1,55 @@
+use std::fmt;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum FaultKind {
+    InvalidOpcode,
+    StackOverflow,
+    StackUnderflow,
+    /// An access starting at the given address runs past the end of memory.
+    MemoryOutOfBounds(u16),
+    /// `SPRITE` with a value that has no font glyph.
+    InvalidFontChar(u8),
+}
+
+/// An error that stops the emulated program, raised by `Chip8State::exec_step`.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Chip8Fault {
+    pub pc: u16,
+    pub opcode: u16,
+    pub kind: FaultKind,
+}
+
+impl fmt::Display for Chip8Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            FaultKind::InvalidOpcode => write!(f, "Invalid opcode")?,
+            FaultKind::StackOverflow => write!(f, "Stack overflow")?,
+            FaultKind::StackUnderflow => write!(f, "Stack underflow")?,
+            FaultKind::MemoryOutOfBounds(addr) => write!(f, "Memory access at 0x{:X} out of bounds", addr)?,
+            FaultKind::InvalidFontChar(c) => write!(f, "No font glyph for 0x{:X}", c)?,
+        }
+        write!(f, " (PC: 0x{:X}, opcode: 0x{:04X})", self.pc, self.opcode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!(
+            "Stack overflow (PC: 0x2A4, opcode: 0x22A4)",
+            format!("{}", Chip8Fault { pc: 0x2a4, opcode: 0x22a4, kind: FaultKind::StackOverflow })
+        );
+        assert_eq!(
+            "Memory access at 0xFFE out of bounds (PC: 0x200, opcode: 0xF233)",
+            format!(
+                "{}",
+                Chip8Fault { pc: 0x200, opcode: 0xf233, kind: FaultKind::MemoryOutOfBounds(0xffe) }
+            )
+        );
+    }
+}
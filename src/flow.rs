@@ -0,0 +1,285 @@
+use opcode::Opcode;
+use program::Chip8Program;
+use state::MAX_PROGRAM_SIZE;
+use util::*;
+use std::collections::BTreeSet;
+
+const PROGRAM_START: u16 = 0x200;
+const DATA_PER_LINE: usize = 8;
+
+/// A line of the `decompile` listing with the bytes it covers. Labels cover none.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct ListingLine {
+    pub addr: u16,
+    pub len: usize,
+    pub code: bool,
+    pub text: String,
+}
+
+/// Result of following control flow through a program from its entry point.
+pub struct Flow {
+    bytes: Vec<u8>,
+    code: Vec<bool>,
+    labels: BTreeSet<u16>,
+}
+
+impl Flow {
+    /// Recursively follows `JMP`, `CALL`, `RET`, `EXIT`, `JMPR` and the skip instructions from 0x200.
+    pub fn analyze(program: &Chip8Program) -> Flow {
+        let mut bytes = program.to_bytes();
+        // Nothing past the end of memory can be loaded or addressed
+        bytes.truncate(MAX_PROGRAM_SIZE);
+        let mut code = vec![false; bytes.len()];
+        let mut labels = BTreeSet::new();
+        let mut pending = vec![PROGRAM_START];
+
+        while let Some(addr) = pending.pop() {
+            if addr < PROGRAM_START {
+                continue;
+            }
+            let offset = (addr - PROGRAM_START) as usize;
+            if offset + 1 >= bytes.len() || code[offset] {
+                continue;
+            }
+            let opcode = match decode_at(&bytes, offset) {
+                Some(opcode) => opcode,
+                None => continue,
+            };
+            code[offset] = true;
+
+            // None for the last instruction in memory, whose successor would wrap around
+            let next = addr.checked_add(opcode.size());
+            match opcode {
+                Opcode::RET | Opcode::EXIT => {}
+                Opcode::JMP(n) | Opcode::JMPR(n) => {
+                    labels.insert(n);
+                    pending.push(n);
+                }
+                Opcode::CALL(n) => {
+                    labels.insert(n);
+                    pending.push(n);
+                    pending.extend(next);
+                }
+                Opcode::SKIPEQ(_, _) | Opcode::SKIPNEQ(_, _) | Opcode::SKIPREQ(_, _) |
+                Opcode::SKIPRNEQ(_, _) | Opcode::SKIPKEQ(_) | Opcode::SKIPKNEQ(_) => {
+                    if let Some(next) = next {
+                        // A skip steps over the whole of a 4 byte F000 NNNN
+                        let offset = (next - PROGRAM_START) as usize;
+                        let skipped = if offset + 1 < bytes.len() {
+                            decode_at(&bytes, offset).map_or(2, |oc| oc.size())
+                        } else {
+                            2
+                        };
+                        pending.push(next);
+                        pending.extend(next.checked_add(skipped));
+                    }
+                }
+                _ => pending.extend(next),
+            }
+        }
+
+        // Only targets that start a listing line get a label, the rest stay numeric
+        let mut inside = vec![false; bytes.len()];
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let size = if code[offset] { decode_at(&bytes, offset).unwrap().size() as usize } else { 1 };
+            for n in offset + 1..(offset + size).min(bytes.len()) {
+                inside[n] = true;
+            }
+            offset += size;
+        }
+        let labels = labels
+            .into_iter()
+            .filter(|&addr| addr >= PROGRAM_START && inside.get((addr - PROGRAM_START) as usize) == Some(&false))
+            .collect();
+
+        Flow { bytes, code, labels }
+    }
+
+    pub fn is_code(&self, addr: u16) -> bool {
+        addr >= PROGRAM_START && self.code.get((addr - PROGRAM_START) as usize) == Some(&true)
+    }
+
+    /// The instruction at `addr` if it is code.
+    pub fn opcode_at(&self, addr: u16) -> Option<Opcode> {
+        if self.is_code(addr) {
+            decode_at(&self.bytes, (addr - PROGRAM_START) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_label(&self, addr: u16) -> bool {
+        self.labels.contains(&addr)
+    }
+
+    /// Renders the program as a `decompile` listing with labels and `DB` lines for data.
+    pub fn listing(&self) -> Vec<String> {
+        self.listing_lines().into_iter().map(|line| line.text).collect()
+    }
+
+    pub fn listing_lines(&self) -> Vec<ListingLine> {
+        let mut lines = vec![];
+        let mut offset = 0;
+
+        while offset < self.bytes.len() {
+            let addr = PROGRAM_START + offset as u16;
+            if self.is_label(addr) {
+                lines.push(ListingLine { addr, len: 0, code: false, text: format!("{}:", label(addr)) });
+            }
+
+            if self.is_code(addr) {
+                let opcode = decode_at(&self.bytes, offset).unwrap();
+                let size = opcode.size() as usize;
+                let hex: Vec<String> =
+                    self.bytes[offset..offset + size].iter().map(|b| format!("{:02x}", b)).collect();
+                let asm = self.symbolic_asm(&opcode);
+                let text = format!("{}: {}   {}", filled_hex(addr), hex.join(" "), asm);
+                lines.push(ListingLine { addr, len: size, code: true, text });
+                offset += size;
+                continue;
+            }
+
+            let mut end = offset + 1;
+            while end < self.bytes.len() && end - offset < DATA_PER_LINE && !self.code[end] &&
+                !self.is_label(PROGRAM_START + end as u16)
+            {
+                end += 1;
+            }
+            let data = &self.bytes[offset..end];
+            let hex: Vec<String> = data.iter().map(|b| format!("{:02x}", b)).collect();
+            let values: Vec<String> = data.iter().map(|b| format!("${:02X}", b)).collect();
+            let text = format!("{}: {}   DB {}", filled_hex(addr), hex.join(" "), values.join(", "));
+            lines.push(ListingLine { addr, len: end - offset, code: false, text });
+            offset = end;
+        }
+
+        lines
+    }
+
+    fn symbolic_asm(&self, opcode: &Opcode) -> String {
+        match *opcode {
+            Opcode::JMP(n) if self.is_label(n) => format!("JMP {}", label(n)),
+            Opcode::CALL(n) if self.is_label(n) => format!("CALL {}", label(n)),
+            Opcode::JMPR(n) if self.is_label(n) => format!("JMPR {}", label(n)),
+            ref other => other.to_asm(),
+        }
+    }
+}
+
+fn word_at(bytes: &[u8], offset: usize) -> u16 {
+    ((bytes[offset] as u16) << 8) | (bytes[offset + 1] as u16)
+}
+
+fn decode_at(bytes: &[u8], offset: usize) -> Option<Opcode> {
+    let next = if offset + 3 < bytes.len() { Some(word_at(bytes, offset + 2)) } else { None };
+    Opcode::decode(word_at(bytes, offset), next)
+}
+
+fn label(addr: u16) -> String {
+    format!("L{:03X}", addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembler::assemble;
+
+    #[test]
+    fn test_analyze() {
+        // CALL $206, JMP $202 (self loop), data $F0, RET
+        let flow = Flow::analyze(&Chip8Program::new(&[0x22, 0x06, 0x12, 0x02, 0xf0, 0x90, 0x00, 0xee]));
+        assert!(flow.is_code(0x200));
+        assert!(flow.is_code(0x202));
+        assert!(!flow.is_code(0x204));
+        assert!(flow.is_code(0x206));
+        assert!(flow.is_label(0x202));
+        assert!(flow.is_label(0x206));
+        assert!(!flow.is_label(0x200));
+    }
+
+    #[test]
+    fn test_skip_and_odd_alignment() {
+        // SKIPEQ V0, #$0 / JMP $205 / data / CLS at an odd address / JMP $205
+        let flow = Flow::analyze(&Chip8Program::new(&[0x30, 0x00, 0x12, 0x05, 0xff, 0x00, 0xe0, 0x12, 0x05, 0x00]));
+        assert!(flow.is_code(0x200));
+        assert!(flow.is_code(0x202));
+        assert!(flow.is_code(0x205));
+        assert!(flow.is_code(0x207));
+        assert!(!flow.is_code(0x204));
+        assert!(!flow.is_code(0x206));
+    }
+
+    #[test]
+    fn test_long_i() {
+        // SKIPEQ V0, #$0 / LONGI $1234 / RET
+        let flow = Flow::analyze(&Chip8Program::new(&[0x30, 0x00, 0xf0, 0x00, 0x12, 0x34, 0x00, 0xee]));
+        assert!(flow.is_code(0x202));
+        assert!(!flow.is_code(0x204));
+        assert!(flow.is_code(0x206));
+        assert_eq!(
+            vec![
+                "0200: 30 00   SKIPEQ V0, #$0",
+                "0202: f0 00 12 34   LONGI $1234",
+                "0206: 00 ee   RET",
+            ],
+            flow.listing()
+        );
+    }
+
+    #[test]
+    fn test_exit() {
+        // EXIT / data
+        let flow = Flow::analyze(&Chip8Program::new(&[0x00, 0xfd, 0xff, 0xff]));
+        assert!(flow.is_code(0x200));
+        assert!(!flow.is_code(0x202));
+    }
+
+    #[test]
+    fn test_listing() {
+        let flow = Flow::analyze(&Chip8Program::new(&[0x22, 0x06, 0x12, 0x02, 0xf0, 0x90, 0x00, 0xee]));
+        assert_eq!(
+            vec![
+                "0200: 22 06   CALL L206",
+                "L202:",
+                "0202: 12 02   JMP L202",
+                "0204: f0 90   DB $F0, $90",
+                "L206:",
+                "0206: 00 ee   RET",
+            ],
+            flow.listing()
+        );
+    }
+
+    #[test]
+    fn test_targets_without_label() {
+        // CALL past the end of the ROM, JMP into the font area
+        let rom = vec![0x22, 0x0a, 0x11, 0x00];
+        let flow = Flow::analyze(&Chip8Program::new(&rom));
+        assert!(!flow.is_label(0x20a));
+        assert!(!flow.is_label(0x100));
+        assert_eq!(vec!["0200: 22 0a   CALL $20A", "0202: 11 00   JMP $100"], flow.listing());
+        assert_eq!(Ok(rom), assemble(&flow.listing().join("\n")));
+    }
+
+    #[test]
+    fn test_full_memory() {
+        // MOV V0, #$1 up to the end of memory, with SKIPEQ V0, #$1 and MOV V0, #$1 in the last words
+        let mut rom = vec![0x60, 0x01].repeat(MAX_PROGRAM_SIZE / 2);
+        rom[MAX_PROGRAM_SIZE - 4] = 0x30;
+        let flow = Flow::analyze(&Chip8Program::new(&rom));
+        assert!(flow.is_code(0xfffc));
+        assert!(flow.is_code(0xfffe));
+        let flow = Flow::analyze(&Chip8Program::new(&vec![0x30, 0x01].repeat(MAX_PROGRAM_SIZE / 2 + 1)));
+        assert!(flow.is_code(0xfffe));
+        assert_eq!(MAX_PROGRAM_SIZE / 2, flow.listing().len());
+    }
+
+    #[test]
+    fn test_listing_reassembles() {
+        let rom = vec![0x30, 0x00, 0x12, 0x05, 0xff, 0x00, 0xe0, 0x12, 0x05, 0x00, 0x3c, 0x42];
+        let flow = Flow::analyze(&Chip8Program::new(&rom));
+        assert_eq!(Ok(rom), assemble(&flow.listing().join("\n")));
+    }
+}
@@ -0,0 +1,144 @@
+use std::fs;
+use std::io;
+use std::io::Read;
+
+/// Size of a full hex font: 16 glyphs of 5 bytes each.
+pub const FONT_SIZE: usize = 16 * 5;
+
+// See http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#2.4 for reference
+pub const DEFAULT: [u8; FONT_SIZE] = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0x90, 0x90, 0xf0, 0x10, 0x10, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0xf0, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // a
+    0xe0, 0x90, 0xe0, 0x90, 0xe0, // b
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // c
+    0xe0, 0x90, 0x90, 0x90, 0xe0, // d
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // e
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // f
+];
+
+pub const COSMAC_VIP: [u8; FONT_SIZE] = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0xa0, 0xa0, 0xf0, 0x20, 0x20, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0xf0, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // a
+    0xf0, 0x50, 0x70, 0x50, 0xf0, // b
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // c
+    0xf0, 0x50, 0x50, 0x50, 0xf0, // d
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // e
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // f
+];
+
+pub const DREAM_6800: [u8; FONT_SIZE] = [
+    0xe0, 0xa0, 0xa0, 0xa0, 0xe0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xe0, 0x20, 0xe0, 0x80, 0xe0, // 2
+    0xe0, 0x20, 0xe0, 0x20, 0xe0, // 3
+    0x80, 0xa0, 0xa0, 0xe0, 0x20, // 4
+    0xe0, 0x80, 0xe0, 0x20, 0xe0, // 5
+    0xe0, 0x80, 0xe0, 0xa0, 0xe0, // 6
+    0xe0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xe0, 0xa0, 0xe0, 0xa0, 0xe0, // 8
+    0xe0, 0xa0, 0xe0, 0x20, 0xe0, // 9
+    0xe0, 0xa0, 0xe0, 0xa0, 0xa0, // a
+    0xc0, 0xa0, 0xe0, 0xa0, 0xc0, // b
+    0xe0, 0x80, 0x80, 0x80, 0xe0, // c
+    0xc0, 0xa0, 0xa0, 0xa0, 0xc0, // d
+    0xe0, 0x80, 0xe0, 0x80, 0xe0, // e
+    0xe0, 0x80, 0xc0, 0x80, 0x80, // f
+];
+
+pub const ETI_660: [u8; FONT_SIZE] = [
+    0xe0, 0xa0, 0xa0, 0xa0, 0xe0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xe0, 0x20, 0xe0, 0x80, 0xe0, // 2
+    0xe0, 0x20, 0xe0, 0x20, 0xe0, // 3
+    0xa0, 0xa0, 0xe0, 0x20, 0x20, // 4
+    0xe0, 0x80, 0xe0, 0x20, 0xe0, // 5
+    0xe0, 0x80, 0xe0, 0xa0, 0xe0, // 6
+    0xe0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xe0, 0xa0, 0xe0, 0xa0, 0xe0, // 8
+    0xe0, 0xa0, 0xe0, 0x20, 0xe0, // 9
+    0xe0, 0xa0, 0xe0, 0xa0, 0xa0, // a
+    0xc0, 0xa0, 0xe0, 0xa0, 0xc0, // b
+    0xe0, 0x80, 0x80, 0x80, 0xe0, // c
+    0xc0, 0xa0, 0xa0, 0xa0, 0xc0, // d
+    0xe0, 0x80, 0xe0, 0x80, 0xe0, // e
+    0xe0, 0x80, 0xc0, 0x80, 0x80, // f
+];
+
+/// Size of the SUPER-CHIP big font: 16 glyphs of 10 bytes each.
+pub const BIG_FONT_SIZE: usize = 16 * 10;
+
+pub const BIG: [u8; BIG_FONT_SIZE] = [
+    0x3c, 0x7e, 0xe7, 0xc3, 0xc3, 0xc3, 0xc3, 0xe7, 0x7e, 0x3c, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, // 1
+    0x3e, 0x7f, 0xc3, 0x06, 0x0c, 0x18, 0x30, 0x60, 0xff, 0xff, // 2
+    0x3c, 0x7e, 0xc3, 0x03, 0x0e, 0x0e, 0x03, 0xc3, 0x7e, 0x3c, // 3
+    0x06, 0x0e, 0x1e, 0x36, 0x66, 0xc6, 0xff, 0xff, 0x06, 0x06, // 4
+    0xff, 0xff, 0xc0, 0xc0, 0xfc, 0xfe, 0x03, 0xc3, 0x7e, 0x3c, // 5
+    0x3e, 0x7c, 0xc0, 0xc0, 0xfc, 0xfe, 0xc3, 0xc3, 0x7e, 0x3c, // 6
+    0xff, 0xff, 0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7e, 0x7e, 0xc3, 0xc3, 0x7e, 0x3c, // 8
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7f, 0x3f, 0x03, 0x03, 0x3e, 0x7c, // 9
+    0x3c, 0x7e, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, // a
+    0xfc, 0xfe, 0xc3, 0xc3, 0xfe, 0xfe, 0xc3, 0xc3, 0xfe, 0xfc, // b
+    0x3c, 0x7e, 0xc3, 0xc0, 0xc0, 0xc0, 0xc0, 0xc3, 0x7e, 0x3c, // c
+    0xfc, 0xfe, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xfe, 0xfc, // d
+    0xff, 0xff, 0xc0, 0xc0, 0xfc, 0xfc, 0xc0, 0xc0, 0xff, 0xff, // e
+    0xff, 0xff, 0xc0, 0xc0, 0xfc, 0xfc, 0xc0, 0xc0, 0xc0, 0xc0, // f
+];
+
+pub fn from_name(name: &str) -> Option<[u8; FONT_SIZE]> {
+    match name {
+        "default" => Some(DEFAULT),
+        "vip" => Some(COSMAC_VIP),
+        "dream6800" => Some(DREAM_6800),
+        "eti660" => Some(ETI_660),
+        _ => None,
+    }
+}
+
+/// Reads a custom font made of exactly `FONT_SIZE` bytes.
+pub fn from_file(path: &str) -> Result<[u8; FONT_SIZE], io::Error> {
+    let mut buf = Vec::new();
+    fs::File::open(path)?.read_to_end(&mut buf)?;
+    if buf.len() != FONT_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("font must be {} bytes, found {}", FONT_SIZE, buf.len()),
+        ));
+    }
+    let mut font = [0; FONT_SIZE];
+    font.copy_from_slice(&buf);
+    Ok(font)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Some(&DEFAULT[..]), from_name("default").as_ref().map(|f| &f[..]));
+        assert_eq!(Some(&COSMAC_VIP[..]), from_name("vip").as_ref().map(|f| &f[..]));
+        assert_eq!(Some(&DREAM_6800[..]), from_name("dream6800").as_ref().map(|f| &f[..]));
+        assert_eq!(Some(&ETI_660[..]), from_name("eti660").as_ref().map(|f| &f[..]));
+        assert!(from_name("fonts/custom.bin").is_none());
+    }
+}
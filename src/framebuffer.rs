@@ -0,0 +1,226 @@
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+/// Mask of every XO-CHIP bitplane.
+pub const ALL_PLANES: u8 = 0b11;
+
+/// The screen, kept outside of the emulated memory so it can change resolution.
+///
+/// Every pixel holds one bit per XO-CHIP bitplane; plain CHIP-8 and SUPER-CHIP only use plane 1.
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    dirty: bool,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer { width, height, pixels: vec![0; width * height], dirty: false }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// True if the screen changed since the last `take_dirty`.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Returns whether the screen changed and resets the flag.
+    pub fn take_dirty(&mut self) -> bool {
+        let dirty = self.dirty;
+        self.dirty = false;
+        dirty
+    }
+
+    /// Changes the resolution, clearing the screen.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        *self = Framebuffer::new(width, height);
+        self.dirty = true;
+    }
+
+    /// Clears the given planes.
+    pub fn clear(&mut self, planes: u8) {
+        for px in self.pixels.iter_mut() {
+            *px &= !planes;
+        }
+        self.dirty = true;
+    }
+
+    /// True if the pixel is set in any plane.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.planes(x, y) != 0
+    }
+
+    /// The planes the pixel is set in, which is its colour index.
+    pub fn planes(&self, x: usize, y: usize) -> u8 {
+        assert!(x < self.width && y < self.height);
+        self.pixels[y * self.width + x]
+    }
+
+    /// Flips a pixel in `plane`, returning true if it was turned off.
+    pub fn xor_pixel(&mut self, x: usize, y: usize, plane: u8) -> bool {
+        assert!(x < self.width && y < self.height);
+        self.dirty = true;
+        let px = &mut self.pixels[y * self.width + x];
+        *px ^= plane;
+        *px & plane == 0
+    }
+
+    /// Sets or clears a pixel in the given planes.
+    pub fn write_pixel(&mut self, x: usize, y: usize, planes: u8, on: bool) {
+        assert!(x < self.width && y < self.height);
+        self.dirty = true;
+        let px = &mut self.pixels[y * self.width + x];
+        if on {
+            *px |= planes;
+        } else {
+            *px &= !planes;
+        }
+    }
+
+    pub fn scroll_down(&mut self, n: usize, planes: u8) {
+        self.dirty = true;
+        let n = n.min(self.height) * self.width;
+        let len = self.pixels.len();
+        for i in (0..len).rev() {
+            let moved = if i >= n { self.pixels[i - n] } else { 0 };
+            self.pixels[i] = (self.pixels[i] & !planes) | (moved & planes);
+        }
+    }
+
+    pub fn scroll_left(&mut self, n: usize, planes: u8) {
+        self.dirty = true;
+        let n = n.min(self.width);
+        for row in self.pixels.chunks_mut(self.width) {
+            for x in 0..row.len() {
+                let moved = if x + n < row.len() { row[x + n] } else { 0 };
+                row[x] = (row[x] & !planes) | (moved & planes);
+            }
+        }
+    }
+
+    pub fn scroll_right(&mut self, n: usize, planes: u8) {
+        self.dirty = true;
+        let n = n.min(self.width);
+        for row in self.pixels.chunks_mut(self.width) {
+            for x in (0..row.len()).rev() {
+                let moved = if x >= n { row[x - n] } else { 0 };
+                row[x] = (row[x] & !planes) | (moved & planes);
+            }
+        }
+    }
+}
+
+/// Screens are equal if they show the same picture, whether or not it was redrawn yet.
+impl PartialEq for Framebuffer {
+    fn eq(&self, other: &Framebuffer) -> bool {
+        self.width == other.width && self.height == other.height && self.pixels == other.pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(fb: &Framebuffer) -> Vec<(usize, usize)> {
+        let mut on = vec![];
+        for y in 0..fb.height() {
+            for x in 0..fb.width() {
+                if fb.pixel(x, y) {
+                    on.push((x, y));
+                }
+            }
+        }
+        on
+    }
+
+    #[test]
+    fn test_xor_pixel() {
+        let mut fb = Framebuffer::new(LORES_WIDTH, LORES_HEIGHT);
+        assert!(!fb.xor_pixel(3, 4, 1));
+        assert!(fb.pixel(3, 4));
+        assert!(fb.xor_pixel(3, 4, 1));
+        assert!(!fb.pixel(3, 4));
+    }
+
+    #[test]
+    fn test_planes() {
+        let mut fb = Framebuffer::new(LORES_WIDTH, LORES_HEIGHT);
+        assert!(!fb.xor_pixel(3, 4, 2));
+        assert!(!fb.xor_pixel(3, 4, 1));
+        assert_eq!(0b11, fb.planes(3, 4));
+        assert!(fb.xor_pixel(3, 4, 2));
+        assert_eq!(0b01, fb.planes(3, 4));
+        fb.xor_pixel(5, 5, 2);
+        fb.clear(1);
+        assert!(!fb.pixel(3, 4));
+        assert_eq!(0b10, fb.planes(5, 5));
+        fb.xor_pixel(5, 5, 1);
+        fb.scroll_down(1, 2);
+        assert_eq!(0b01, fb.planes(5, 5));
+        assert_eq!(0b10, fb.planes(5, 6));
+        fb.clear(ALL_PLANES);
+        assert!(lit(&fb).is_empty());
+    }
+
+    #[test]
+    fn test_write_pixel() {
+        let mut fb = Framebuffer::new(LORES_WIDTH, LORES_HEIGHT);
+        fb.write_pixel(3, 4, ALL_PLANES, true);
+        assert_eq!(0b11, fb.planes(3, 4));
+        fb.write_pixel(3, 4, 1, false);
+        assert_eq!(0b10, fb.planes(3, 4));
+    }
+
+    #[test]
+    fn test_dirty() {
+        let mut fb = Framebuffer::new(LORES_WIDTH, LORES_HEIGHT);
+        assert!(!fb.is_dirty());
+        fb.xor_pixel(3, 4, 1);
+        assert!(fb.take_dirty());
+        assert!(!fb.take_dirty());
+        fb.scroll_left(4, 1);
+        assert!(fb.take_dirty());
+        fb.clear(ALL_PLANES);
+        assert!(fb.take_dirty());
+        fb.resize(HIRES_WIDTH, HIRES_HEIGHT);
+        assert!(fb.is_dirty());
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut fb = Framebuffer::new(LORES_WIDTH, LORES_HEIGHT);
+        fb.xor_pixel(3, 4, 1);
+        fb.resize(HIRES_WIDTH, HIRES_HEIGHT);
+        assert_eq!(HIRES_WIDTH, fb.width());
+        assert_eq!(HIRES_HEIGHT, fb.height());
+        assert!(lit(&fb).is_empty());
+        fb.xor_pixel(127, 63, 1);
+        assert_eq!(vec![(127, 63)], lit(&fb));
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut fb = Framebuffer::new(LORES_WIDTH, LORES_HEIGHT);
+        fb.xor_pixel(0, 0, 1);
+        fb.xor_pixel(63, 31, 1);
+        fb.scroll_down(2, ALL_PLANES);
+        assert_eq!(vec![(0, 2)], lit(&fb));
+        fb.scroll_right(4, ALL_PLANES);
+        assert_eq!(vec![(4, 2)], lit(&fb));
+        fb.scroll_left(3, ALL_PLANES);
+        assert_eq!(vec![(1, 2)], lit(&fb));
+        fb.scroll_left(4, ALL_PLANES);
+        assert!(lit(&fb).is_empty());
+    }
+}
@@ -0,0 +1,548 @@
+use state::Chip8State;
+use debugger::Debugger;
+use fault::{Chip8Fault, FaultKind};
+use io::Chip8Io;
+use instrument::Instrumentation;
+use std::io;
+use std::io::{Read, Write};
+use std::thread;
+use std::time::Duration;
+
+/// Register numbers in `g`/`p` packets: V0-VF, then I, PC, SP, DT and ST.
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+const REG_DT: usize = 19;
+const REG_ST: usize = 20;
+const REG_COUNT: usize = 21;
+
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/// Describes the register file to clients that ask for `target.xml`, 16-bit registers are
+/// little-endian.
+const TARGET_XML: &str = "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+<target version=\"1.0\"><feature name=\"org.chip8.core\">\
+<reg name=\"v0\" bitsize=\"8\" regnum=\"0\"/><reg name=\"v1\" bitsize=\"8\"/>\
+<reg name=\"v2\" bitsize=\"8\"/><reg name=\"v3\" bitsize=\"8\"/>\
+<reg name=\"v4\" bitsize=\"8\"/><reg name=\"v5\" bitsize=\"8\"/>\
+<reg name=\"v6\" bitsize=\"8\"/><reg name=\"v7\" bitsize=\"8\"/>\
+<reg name=\"v8\" bitsize=\"8\"/><reg name=\"v9\" bitsize=\"8\"/>\
+<reg name=\"va\" bitsize=\"8\"/><reg name=\"vb\" bitsize=\"8\"/>\
+<reg name=\"vc\" bitsize=\"8\"/><reg name=\"vd\" bitsize=\"8\"/>\
+<reg name=\"ve\" bitsize=\"8\"/><reg name=\"vf\" bitsize=\"8\"/>\
+<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/><reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>\
+<reg name=\"sp\" bitsize=\"8\"/><reg name=\"dt\" bitsize=\"8\"/><reg name=\"st\" bitsize=\"8\"/>\
+</feature></target>";
+
+/// What the client asked for besides debugging the program.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum GdbStatus {
+    Attached,
+    /// The client detached or hung up, the program keeps running.
+    Detached,
+    /// The client sent `k`, the emulator should quit.
+    Killed,
+}
+
+/// A GDB remote serial protocol server driving a `Debugger`.
+///
+/// The stream must be non-blocking: `poll` handles whatever packets have arrived and returns,
+/// so the frontend keeps running frames while the client waits for a stop reply.
+pub struct GdbStub<S: Read + Write> {
+    stream: S,
+    input: Vec<u8>,
+    /// Set while the client waits for the program to stop after `c`.
+    running: bool,
+}
+
+impl<S: Read + Write> GdbStub<S> {
+    pub fn new(stream: S) -> GdbStub<S> {
+        GdbStub { stream, input: vec![], running: false }
+    }
+
+    /// Handles pending packets and sends a stop reply once a continue hit a breakpoint. Steps
+    /// are reported to `instruments` like any other instruction.
+    pub fn poll<T: Chip8Io, W: Write>(
+        &mut self,
+        state: &mut Chip8State,
+        debugger: &mut Debugger,
+        io: &mut T,
+        instruments: &mut Instrumentation<W>,
+    ) -> Result<GdbStatus, io::Error> {
+        let mut buf = [0; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(GdbStatus::Detached),
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        while let Some(packet) = self.next_packet()? {
+            let status = self.handle(&packet, state, debugger, io, instruments)?;
+            if status != GdbStatus::Attached {
+                return Ok(status);
+            }
+        }
+        if self.running && debugger.paused() {
+            self.running = false;
+            self.send(&format!("S{:02x}", SIGTRAP))?;
+        }
+        Ok(GdbStatus::Attached)
+    }
+
+    /// Stops the program and tells the client about a fault hit while running.
+    pub fn report_fault(&mut self, fault: &Chip8Fault, debugger: &mut Debugger) -> Result<(), io::Error> {
+        debugger.pause();
+        self.running = false;
+        self.send(&format!("S{:02x}", signal(fault)))
+    }
+
+    /// Takes the next complete packet from the input, acknowledging it. The interrupt byte
+    /// (Ctrl-C) comes back as an empty packet named "\x03".
+    fn next_packet(&mut self) -> Result<Option<String>, io::Error> {
+        loop {
+            match self.input.first() {
+                None => return Ok(None),
+                Some(&0x03) => {
+                    self.input.remove(0);
+                    return Ok(Some("\x03".to_string()));
+                }
+                Some(&b'$') => break,
+                // Acks and line noise
+                Some(_) => {
+                    self.input.remove(0);
+                }
+            }
+        }
+        let end = match self.input.iter().position(|&b| b == b'#') {
+            Some(end) if end + 2 < self.input.len() => end,
+            _ => return Ok(None),
+        };
+        let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+        let data = &packet[1..end];
+        let sum = String::from_utf8_lossy(&packet[end + 1..]).to_string();
+        if u8::from_str_radix(&sum, 16).ok() != Some(checksum(data)) {
+            self.write(b"-")?;
+            return self.next_packet();
+        }
+        self.write(b"+")?;
+        Ok(Some(String::from_utf8_lossy(data).to_string()))
+    }
+
+    fn send(&mut self, data: &str) -> Result<(), io::Error> {
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        self.write(packet.as_bytes())
+    }
+
+    /// Writes all of `data`, waiting while a non-blocking stream's send buffer is full.
+    fn write(&mut self, mut data: &[u8]) -> Result<(), io::Error> {
+        while !data.is_empty() {
+            match self.stream.write(data) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "connection closed")),
+                Ok(n) => data = &data[n..],
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(1)),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.stream.flush()
+    }
+
+    fn handle<T: Chip8Io, W: Write>(
+        &mut self,
+        packet: &str,
+        state: &mut Chip8State,
+        debugger: &mut Debugger,
+        io: &mut T,
+        instruments: &mut Instrumentation<W>,
+    ) -> Result<GdbStatus, io::Error> {
+        let (cmd, args) = packet.split_at(packet.chars().next().map_or(0, |c| c.len_utf8()));
+        // What the client changes isn't part of the last instruction's trace record or coverage
+        match cmd {
+            "G" | "P" | "M" | "c" | "s" => instruments.complete(state),
+            _ => {}
+        }
+        let reply = match cmd {
+            "\x03" => {
+                debugger.pause();
+                // The stop reply goes out at the end of `poll`
+                return Ok(GdbStatus::Attached);
+            }
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => hex(&registers(state)),
+            "G" => match unhex(args) {
+                Some(ref bytes) if bytes.len() == REG_COUNT + 2 => {
+                    for n in 0..REG_COUNT {
+                        let at = reg_offset(n);
+                        let size = reg_size(n);
+                        set_register(state, n, &bytes[at..at + size]);
+                    }
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(n) if n < REG_COUNT => {
+                    let at = reg_offset(n);
+                    hex(&registers(state)[at..at + reg_size(n)])
+                }
+                _ => "E01".to_string(),
+            },
+            "P" => {
+                let mut parts = args.splitn(2, '=');
+                let n = parts.next().and_then(|n| usize::from_str_radix(n, 16).ok());
+                let value = parts.next().and_then(unhex);
+                match (n, value) {
+                    (Some(n), Some(ref value)) if n < REG_COUNT && value.len() == reg_size(n) => {
+                        set_register(state, n, value);
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "m" => match parse_range(args).and_then(|(addr, len)| mem_range(state, addr, len)) {
+                Some((start, end)) => hex(&state.mem[start..end]),
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let mut parts = args.splitn(2, ':');
+                let range = parts.next().and_then(parse_range);
+                let data = parts.next().and_then(unhex);
+                match (range.and_then(|(addr, len)| mem_range(state, addr, len)), data) {
+                    (Some((start, end)), Some(ref data)) if data.len() == end - start => {
+                        state.mem[start..end].copy_from_slice(data);
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "c" => {
+                if let Some(addr) = parse_u16(args) {
+                    state.pc = addr;
+                }
+                debugger.resume(state);
+                self.running = true;
+                return Ok(GdbStatus::Attached);
+            }
+            "s" => {
+                if let Some(addr) = parse_u16(args) {
+                    state.pc = addr;
+                }
+                instruments.before_instruction(state);
+                match debugger.step(state, io) {
+                    Ok(()) => format!("S{:02x}", SIGTRAP),
+                    Err(fault) => format!("S{:02x}", signal(&fault)),
+                }
+            }
+            "Z" | "z" => {
+                let fields: Vec<&str> = args.split(',').collect();
+                match (fields.get(0), fields.get(1).and_then(|a| parse_u16(a))) {
+                    // Software and hardware breakpoints behave the same here
+                    (Some(&"0"), Some(addr)) | (Some(&"1"), Some(addr)) => {
+                        if cmd == "Z" {
+                            debugger.add_breakpoint(addr);
+                        } else {
+                            debugger.remove_breakpoint(addr);
+                        }
+                        "OK".to_string()
+                    }
+                    _ => String::new(),
+                }
+            }
+            "D" => {
+                self.send("OK")?;
+                debugger.resume(state);
+                return Ok(GdbStatus::Detached);
+            }
+            "k" => return Ok(GdbStatus::Killed),
+            "H" => "OK".to_string(),
+            "q" => query(args),
+            // An empty reply tells the client the packet isn't supported
+            _ => String::new(),
+        };
+        self.send(&reply)?;
+        Ok(GdbStatus::Attached)
+    }
+}
+
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        "PacketSize=4000;qXfer:features:read+".to_string()
+    } else if args == "Attached" {
+        "1".to_string()
+    } else if args == "C" {
+        "QC1".to_string()
+    } else if args.starts_with("Xfer:features:read:target.xml:") {
+        let range = &args["Xfer:features:read:target.xml:".len()..];
+        match parse_range(range) {
+            Some((offset, len)) => {
+                let offset = (offset as usize).min(TARGET_XML.len());
+                let end = (offset + len).min(TARGET_XML.len());
+                let more = if end < TARGET_XML.len() { "m" } else { "l" };
+                format!("{}{}", more, &TARGET_XML[offset..end])
+            }
+            None => "E01".to_string(),
+        }
+    } else {
+        String::new()
+    }
+}
+
+fn signal(fault: &Chip8Fault) -> u8 {
+    match fault.kind {
+        FaultKind::InvalidOpcode => SIGILL,
+        _ => SIGSEGV,
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len() / 2)
+        .map(|n| s.get(n * 2..n * 2 + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+fn parse_u16(s: &str) -> Option<u16> {
+    u16::from_str_radix(s, 16).ok()
+}
+
+/// Parses `ADDR,LENGTH` in hex.
+fn parse_range(s: &str) -> Option<(u32, usize)> {
+    let mut parts = s.splitn(2, ',');
+    let addr = parts.next().and_then(|a| u32::from_str_radix(a, 16).ok())?;
+    let len = parts.next().and_then(|l| usize::from_str_radix(l, 16).ok())?;
+    Some((addr, len))
+}
+
+fn mem_range(state: &Chip8State, addr: u32, len: usize) -> Option<(usize, usize)> {
+    let start = addr as usize;
+    if start + len > state.mem.len() {
+        return None;
+    }
+    Some((start, start + len))
+}
+
+fn reg_size(n: usize) -> usize {
+    if n == REG_I || n == REG_PC { 2 } else { 1 }
+}
+
+fn reg_offset(n: usize) -> usize {
+    (0..n).map(reg_size).sum()
+}
+
+/// The register file as sent in a `g` reply.
+fn registers(state: &Chip8State) -> Vec<u8> {
+    let mut regs = state.vregs.to_vec();
+    regs.extend_from_slice(&[state.i as u8, (state.i >> 8) as u8]);
+    regs.extend_from_slice(&[state.pc as u8, (state.pc >> 8) as u8]);
+    regs.extend_from_slice(&[state.sp, state.delay, state.sound]);
+    regs
+}
+
+fn set_register(state: &mut Chip8State, n: usize, value: &[u8]) {
+    let word = || value[0] as u16 | (value[1] as u16) << 8;
+    match n {
+        REG_I => state.i = word(),
+        REG_PC => state.pc = word(),
+        REG_SP => state.sp = value[0],
+        REG_DT => state.delay = value[0],
+        REG_ST => state.sound = value[0],
+        v => state.vregs[v] = value[0],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use io::NullIo;
+    use program::Chip8Program;
+    use trace::Tracer;
+    use std::io::Cursor;
+
+    /// A client connection: reads what the test queued, records what the stub sends.
+    struct Client {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+        /// Makes writes take at most 16 bytes and fail with `WouldBlock` every other time, like
+        /// a socket with a full send buffer.
+        congested: bool,
+        blocked: bool,
+    }
+
+    impl Read for Client {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.input.read(buf)? {
+                0 => Err(io::Error::new(io::ErrorKind::WouldBlock, "no data")),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl Write for Client {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.congested {
+                return self.output.write(buf);
+            }
+            self.blocked = !self.blocked;
+            if self.blocked {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "buffer full"));
+            }
+            self.output.write(&buf[..buf.len().min(16)])
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn packet(data: &str) -> String {
+        format!("${}#{:02x}", data, checksum(data.as_bytes()))
+    }
+
+    /// Sends `packets` and returns everything the stub replied.
+    fn exchange(stub: &mut GdbStub<Client>, state: &mut Chip8State, debugger: &mut Debugger, packets: &[&str]) -> String {
+        let input: String = packets.iter().map(|p| packet(p)).collect();
+        stub.stream.input = Cursor::new(input.into_bytes());
+        stub.stream.output.clear();
+        assert_eq!(GdbStatus::Attached, stub.poll(state, debugger, &mut NullIo, &mut no_instruments()).unwrap());
+        String::from_utf8(stub.stream.output.clone()).unwrap()
+    }
+
+    fn no_instruments() -> Instrumentation<Vec<u8>> {
+        Instrumentation::new()
+    }
+
+    fn setup() -> (GdbStub<Client>, Chip8State, Debugger) {
+        let mut state = Chip8State::new();
+        // 200: ADD V0, #$1, 202: JMP $200
+        state.load_program(&Chip8Program::new(&[0x70, 0x01, 0x12, 0x00])).unwrap();
+        let client = Client { input: Cursor::new(vec![]), output: vec![], congested: false, blocked: false };
+        (GdbStub::new(client), state, Debugger::new())
+    }
+
+    #[test]
+    fn test_registers() {
+        let (mut stub, mut state, mut debugger) = setup();
+        state.vregs[0x1] = 0xab;
+        state.i = 0x1234;
+        let regs = "00ab000000000000000000000000000034120002000000";
+        assert_eq!(format!("+{}", packet(regs)), exchange(&mut stub, &mut state, &mut debugger, &["g"]));
+        assert_eq!(
+            format!("+{}+{}", packet("OK"), packet("0003")),
+            exchange(&mut stub, &mut state, &mut debugger, &["P11=0003", "p11"])
+        );
+        assert_eq!(0x300, state.pc);
+        assert_eq!(format!("+{}", packet("E01")), exchange(&mut stub, &mut state, &mut debugger, &["p15"]));
+    }
+
+    #[test]
+    fn test_memory() {
+        let (mut stub, mut state, mut debugger) = setup();
+        assert_eq!(format!("+{}", packet("70011200")), exchange(&mut stub, &mut state, &mut debugger, &["m200,4"]));
+        assert_eq!(format!("+{}", packet("OK")), exchange(&mut stub, &mut state, &mut debugger, &["M300,2:beef"]));
+        assert_eq!(&[0xbe, 0xef], &state.mem[0x300..0x302]);
+        assert_eq!(format!("+{}", packet("E01")), exchange(&mut stub, &mut state, &mut debugger, &["mffff,2"]));
+    }
+
+    #[test]
+    fn test_congested_stream() {
+        let (mut stub, mut state, mut debugger) = setup();
+        stub.stream.congested = true;
+        let reply = exchange(&mut stub, &mut state, &mut debugger, &["m200,100"]);
+        assert_eq!(format!("+{}", packet(&hex(&state.mem[0x200..0x300]))), reply);
+    }
+
+    #[test]
+    fn test_step_and_continue() {
+        let (mut stub, mut state, mut debugger) = setup();
+        assert_eq!(format!("+{}", packet("S05")), exchange(&mut stub, &mut state, &mut debugger, &["s"]));
+        assert_eq!(0x202, state.pc);
+
+        assert_eq!(
+            format!("+{}+", packet("OK")),
+            exchange(&mut stub, &mut state, &mut debugger, &["Z0,202,2", "c"])
+        );
+        assert!(!debugger.paused());
+        // The frontend runs until the breakpoint, then the next poll reports the stop
+        while debugger.before_instruction(&state) {
+            state.exec_step(&mut NullIo).unwrap();
+        }
+        assert_eq!(packet("S05"), exchange(&mut stub, &mut state, &mut debugger, &[]));
+        assert_eq!(0x202, state.pc);
+        assert_eq!(2, state.vregs[0x0]);
+    }
+
+    #[test]
+    fn test_fault() {
+        let (mut stub, mut state, mut debugger) = setup();
+        state.mem[0x202] = 0xff;
+        state.mem[0x203] = 0xff;
+        exchange(&mut stub, &mut state, &mut debugger, &["c"]);
+        // The frontend hands the fault to the client instead of quitting
+        let mut fault = None;
+        while debugger.before_instruction(&state) {
+            if let Err(f) = state.exec_step(&mut NullIo) {
+                fault = Some(f);
+                break;
+            }
+        }
+        stub.stream.output.clear();
+        stub.report_fault(&fault.unwrap(), &mut debugger).unwrap();
+        assert!(debugger.paused());
+        assert_eq!(packet("S04"), String::from_utf8(stub.stream.output.clone()).unwrap());
+        assert_eq!(format!("+{}", packet("0202")), exchange(&mut stub, &mut state, &mut debugger, &["p11"]));
+        assert_eq!(format!("+{}", packet("S04")), exchange(&mut stub, &mut state, &mut debugger, &["s"]));
+        assert_eq!(0x202, state.pc);
+    }
+
+    #[test]
+    fn test_step_is_instrumented() {
+        let (mut stub, mut state, mut debugger) = setup();
+        let mut out = vec![];
+        {
+            let mut instruments = Instrumentation::new();
+            instruments.tracer = Some(Tracer::new(&mut out, &state));
+            let packets: String = ["s", "P0=ff", "s"].iter().map(|p| packet(p)).collect();
+            stub.stream.input = Cursor::new(packets.into_bytes());
+            stub.poll(&mut state, &mut debugger, &mut NullIo, &mut instruments).unwrap();
+            instruments.finish(&state).unwrap();
+        }
+        // The header and both steps, the write to V0 isn't a change made by ADD
+        let lines: Vec<&str> = ::std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(3, lines.len());
+        assert!(lines[1].contains("\"changes\":{\"V0\":[0,1]}"));
+        assert!(lines[2].contains("\"changes\":{}"));
+    }
+
+    #[test]
+    fn test_interrupt_and_bad_checksum() {
+        let (mut stub, mut state, mut debugger) = setup();
+        exchange(&mut stub, &mut state, &mut debugger, &["c"]);
+        stub.stream.input = Cursor::new(b"\x03$g#00".to_vec());
+        stub.stream.output.clear();
+        stub.poll(&mut state, &mut debugger, &mut NullIo, &mut no_instruments()).unwrap();
+        assert!(debugger.paused());
+        assert_eq!(format!("-{}", packet("S05")), String::from_utf8(stub.stream.output.clone()).unwrap());
+    }
+
+    #[test]
+    fn test_target_xml() {
+        let reply = query("Xfer:features:read:target.xml:0,10");
+        assert_eq!("m<?xml versi", &reply[..12]);
+        assert_eq!("l", query(&format!("Xfer:features:read:target.xml:{:x},10", TARGET_XML.len())));
+    }
+}
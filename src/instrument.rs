@@ -0,0 +1,113 @@
+use state::Chip8State;
+use trace::Tracer;
+use coverage::Coverage;
+use profiler::Profiler;
+use audio::WavRecorder;
+use std::io;
+use std::io::Write;
+
+/// The tools that watch a ROM run, each enabled by setting it.
+///
+/// Every path that executes an instruction, be it a frame, a debugger step or a gdb step,
+/// calls `before_instruction` ahead of it, and `end_frame` after every complete 60 Hz frame.
+pub struct Instrumentation<W: Write> {
+    pub tracer: Option<Tracer<W>>,
+    pub coverage: Option<Coverage>,
+    pub profiler: Option<Profiler>,
+    pub wav: Option<WavRecorder>,
+}
+
+impl<W: Write> Instrumentation<W> {
+    pub fn new() -> Instrumentation<W> {
+        Instrumentation { tracer: None, coverage: None, profiler: None, wav: None }
+    }
+
+    pub fn before_instruction(&mut self, state: &Chip8State) {
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.before_instruction(state);
+        }
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.before_instruction(state);
+        }
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.before_instruction(state);
+        }
+        if let Some(ref mut wav) = self.wav {
+            wav.before_instruction(state);
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.end_frame();
+        }
+        if let Some(ref mut wav) = self.wav {
+            wav.end_frame();
+        }
+    }
+
+    /// Records the last instruction now, for when the state is about to change outside of
+    /// instructions, such as a rewind or loading a save state.
+    pub fn complete(&mut self, state: &Chip8State) {
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.complete(state);
+        }
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.complete(state);
+        }
+    }
+
+    /// Records the last instruction and flushes the trace, returning its first write error.
+    pub fn finish(&mut self, state: &Chip8State) -> Result<(), io::Error> {
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.complete(state);
+        }
+        match self.tracer {
+            Some(ref mut tracer) => tracer.finish(state),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> Default for Instrumentation<W> {
+    fn default() -> Instrumentation<W> {
+        Instrumentation::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use io::NullIo;
+    use program::Chip8Program;
+    use scheduler::Scheduler;
+
+    #[test]
+    fn test_instrumentation() {
+        let mut state = Chip8State::new();
+        // ADD V0, #$1, JMP $200
+        state.load_program(&Chip8Program::new(&[0x70, 0x01, 0x12, 0x00])).unwrap();
+        let mut out = vec![];
+        {
+            let mut instruments = Instrumentation::new();
+            instruments.tracer = Some(Tracer::new(&mut out, &state));
+            instruments.profiler = Some(Profiler::new());
+            instruments.wav = Some(WavRecorder::new());
+            let mut scheduler = Scheduler::new(3);
+            for _ in 0..2 {
+                scheduler
+                    .run_frame_while(&mut state, &mut NullIo, |s| {
+                        instruments.before_instruction(s);
+                        true
+                    })
+                    .unwrap();
+                instruments.end_frame();
+            }
+            instruments.finish(&state).unwrap();
+            assert_eq!(2.0 / 60.0, instruments.wav.as_ref().unwrap().duration());
+            assert_eq!("main 6\n", instruments.profiler.as_ref().unwrap().folded());
+        }
+        // The header and a record per instruction
+        assert_eq!(7, out.iter().filter(|&&b| b == b'\n').count());
+    }
+}
@@ -0,0 +1,114 @@
+use state::Chip8State;
+
+/// Connects a `Chip8State` to a frontend. Every method has a default that does nothing.
+pub trait Chip8Io {
+    /// Called before every instruction to refresh the pressed state of the hex keypad.
+    fn update_keys(&mut self, _keys: &mut [bool; 16]) {}
+
+    /// Called after each step in which `GKEY` is still waiting for a key. May block until input arrives.
+    fn wait_key(&mut self) {}
+
+    /// Called when the sound timer starts (`true`) or stops (`false`).
+    fn sound(&mut self, _on: bool) {}
+
+    /// Called after an instruction changed the screen.
+    fn frame_ready(&mut self, _state: &Chip8State) {}
+}
+
+/// IO for headless runs: no input, no output.
+pub struct NullIo;
+
+impl Chip8Io for NullIo {}
+
+/// A key press or release applied at the start of a frame.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// IO for headless runs that replays a fixed key script.
+pub struct ScriptedIo {
+    events: Vec<KeyEvent>,
+    next_event: usize,
+    keys: [bool; 16],
+    /// The current frame, advanced by the caller.
+    pub frame: u64,
+}
+
+impl ScriptedIo {
+    pub fn new(mut events: Vec<KeyEvent>) -> ScriptedIo {
+        events.sort_by_key(|e| e.frame);
+        ScriptedIo { events, next_event: 0, keys: [false; 16], frame: 0 }
+    }
+
+    /// Parses a script like `10:+5,12:-5`: press key 5 at frame 10 and release it at frame 12.
+    pub fn parse(script: &str) -> Result<ScriptedIo, String> {
+        let mut events = vec![];
+        for item in script.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let err = || format!("Invalid key event \"{}\", expected FRAME:+KEY or FRAME:-KEY", item);
+            let colon = item.find(':').ok_or_else(&err)?;
+            let frame = item[..colon].parse::<u64>().map_err(|_| err())?;
+            let action = &item[colon + 1..];
+            let pressed = match action.chars().next() {
+                Some('+') => true,
+                Some('-') => false,
+                _ => return Err(err()),
+            };
+            let key = u8::from_str_radix(&action[1..], 16).map_err(|_| err())?;
+            if key > 0xf {
+                return Err(err());
+            }
+            events.push(KeyEvent { frame, key, pressed });
+        }
+        Ok(ScriptedIo::new(events))
+    }
+}
+
+impl Chip8Io for ScriptedIo {
+    fn update_keys(&mut self, keys: &mut [bool; 16]) {
+        while self.next_event < self.events.len() && self.events[self.next_event].frame <= self.frame {
+            let event = &self.events[self.next_event];
+            self.keys[event.key as usize] = event.pressed;
+            self.next_event += 1;
+        }
+        *keys = self.keys;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let io = ScriptedIo::parse("12:-a, 10:+A").unwrap();
+        assert_eq!(
+            vec![
+                KeyEvent { frame: 10, key: 0xa, pressed: true },
+                KeyEvent { frame: 12, key: 0xa, pressed: false },
+            ],
+            io.events
+        );
+        assert!(ScriptedIo::parse("").unwrap().events.is_empty());
+        assert!(ScriptedIo::parse("10:5").is_err());
+        assert!(ScriptedIo::parse("10:+10").is_err());
+        assert!(ScriptedIo::parse("x:+1").is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut io = ScriptedIo::parse("1:+5,3:-5").unwrap();
+        let mut keys = [false; 16];
+        io.update_keys(&mut keys);
+        assert!(!keys[0x5]);
+        io.frame = 2;
+        io.update_keys(&mut keys);
+        assert!(keys[0x5]);
+        io.frame = 3;
+        io.update_keys(&mut keys);
+        assert!(!keys[0x5]);
+    }
+}
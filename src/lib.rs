@@ -0,0 +1,66 @@
+//! A CHIP-8, SUPER-CHIP and XO-CHIP interpreter, assembler and decompiler.
+//!
+//! `Chip8State` is the machine. Load a `Chip8Program` into it and call `exec_step` once per
+//! instruction, passing a `Chip8Io` for keys and sound, or let a `scheduler::Scheduler` run
+//! whole 60 Hz frames. `Opcode` decodes and encodes single instructions.
+//!
+//! ```
+//! use chip8::{Chip8Program, Chip8State, Opcode};
+//! use chip8::io::NullIo;
+//!
+//! // MOV V0, #$2A
+//! let program = Chip8Program::new(&[0x60, 0x2a]);
+//! assert_eq!("MOV V0, #$2A", Opcode::new(program.instructions[0]).unwrap().to_asm());
+//!
+//! let mut state = Chip8State::new();
+//! state.load_program(&program).unwrap();
+//! state.exec_step(&mut NullIo).unwrap();
+//! assert_eq!(0x2a, state.vregs[0]);
+//! ```
+//!
+//! The terminal frontend in `display` needs the `ncurses` feature, which is on by default.
+//! Build with `default-features = false` for the core alone.
+
+#![cfg_attr(test, feature(test))]
+#[cfg(test)]
+extern crate test;
+
+#[macro_use]
+extern crate log;
+extern crate rand;
+#[cfg(feature = "ncurses")]
+extern crate ncurses;
+
+pub mod opcode;
+pub mod register;
+pub(crate) mod util;
+pub mod state;
+pub mod program;
+#[cfg(feature = "ncurses")]
+pub mod display;
+pub mod assembler;
+pub mod flow;
+pub mod font;
+pub mod io;
+pub mod fault;
+pub mod clock;
+pub mod scheduler;
+pub mod rng;
+pub mod quirks;
+pub mod framebuffer;
+pub mod screenshot;
+pub mod savestate;
+pub mod rewind;
+pub mod debugger;
+pub mod gdb;
+pub mod trace;
+pub mod coverage;
+pub mod profiler;
+pub mod audio;
+pub mod instrument;
+
+pub use state::Chip8State;
+pub use program::Chip8Program;
+pub use opcode::Opcode;
+pub use register::VReg;
+pub use io::Chip8Io;
@@ -1,29 +1,21 @@
-#![feature(test)]
-extern crate test;
-
+extern crate chip8;
 extern crate clap;
 #[macro_use]
 extern crate log;
 extern crate env_logger;
-extern crate rand;
-extern crate ncurses;
-extern crate chrono;
-
-use clap::{Arg, App, SubCommand};
 
+use clap::{Arg, ArgGroup, App, SubCommand};
+use chip8::{assembler, audio, clock, coverage, debugger, flow, font, instrument, io, opcode, profiler, program,
+            quirks, scheduler, screenshot, state, trace};
+#[cfg(feature = "ncurses")]
+use chip8::{display, fault, gdb, rewind};
 
-mod opcode;
-mod register;
 mod commands;
-mod util;
-mod state;
-mod program;
-mod display;
 
 fn main() {
     env_logger::init().unwrap();
 
-    let matches = App::new("chip8")
+    let app = App::new("chip8")
         .version("0.1.0")
         .author("Dominik Winecki <dominikwinecki@gmail.com>")
         .about("A CHIP8 emulator")
@@ -34,18 +26,83 @@ fn main() {
                     .short("i")
                     .value_name("INPUT")
                     .help("The file to be decompiled. If - then stdin is read.")
-                    .required(true)),
+                    .required(true))
+                .arg(Arg::with_name("flow")
+                    .long("flow")
+                    .help("Follow control flow from 0x200, printing unreachable bytes as data")),
         )
         .subcommand(
-            SubCommand::with_name("exec")
-                .about("Run the program with an ncurses interface")
+            SubCommand::with_name("assemble")
+                .about("Assembles a source file into a binary ROM")
                 .arg(Arg::with_name("input")
                     .short("i")
                     .value_name("INPUT")
-                    .help("The file to be decompiled. If - then stdin is read.")
+                    .help("The file to be assembled. If - then stdin is read.")
+                    .required(true))
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .value_name("OUTPUT")
+                    .help("The ROM file to write. If - then stdout is written.")
                     .required(true)),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Run the program without a terminal and print the final screen and registers")
+                .arg(Arg::with_name("input")
+                    .short("i")
+                    .value_name("INPUT")
+                    .help("The file to be run. If - then stdin is read.")
+                    .required(true))
+                .arg(Arg::with_name("instructions")
+                    .long("instructions")
+                    .value_name("N")
+                    .help("Stop after N instructions"))
+                .arg(Arg::with_name("frames")
+                    .long("frames")
+                    .value_name("N")
+                    .help("Stop after N frames"))
+                .arg(Arg::with_name("until-pc")
+                    .long("until-pc")
+                    .value_name("ADDR")
+                    .help("Stop when PC reaches the hex address ADDR"))
+                .group(ArgGroup::with_name("limit")
+                    .args(&["instructions", "frames", "until-pc"])
+                    .multiple(true)
+                    .required(true))
+                .arg(Arg::with_name("keys")
+                    .long("keys")
+                    .value_name("SCRIPT")
+                    .help("Key presses and releases by frame, e.g. 10:+5,12:-5"))
+                .arg(Arg::with_name("screen")
+                    .long("screen")
+                    .value_name("FILE")
+                    .help("Write the screen to FILE as PNG or PBM (by extension) or ASCII instead of stdout"))
+                .arg(Arg::with_name("font")
+                    .long("font")
+                    .value_name("FONT")
+                    .help("The hex font: default, vip, dream6800, eti660 or the path to an 80 byte file."))
+                .arg(Arg::with_name("ipf")
+                    .long("ipf")
+                    .value_name("N")
+                    .help("Instructions executed per 60 Hz frame (default 10)"))
+                .arg(Arg::with_name("seed")
+                    .long("seed")
+                    .value_name("SEED")
+                    .help("Seed for the RAND instruction, for reproducible runs"))
+                .args(&instrument_args())
+                .arg(Arg::with_name("wav")
+                    .long("wav")
+                    .value_name("FILE")
+                    .help("Render the sound of the run to FILE as a 44.1 kHz WAV"))
+                .arg(Arg::with_name("profile")
+                    .long("profile")
+                    .value_name("PROFILE")
+                    .possible_values(&["vip", "chip48", "schip", "xochip"])
+                    .help("Interpreter quirks to emulate (default vip)")),
+        );
+    #[cfg(feature = "ncurses")]
+    let app = app.subcommand(exec_subcommand());
+    let matches = app.get_matches();
 
     if matches.subcommand_name().is_none() {
         println!("No command found!\n\n{}", matches.usage());
@@ -58,9 +115,109 @@ fn main() {
         "decompile" => commands::decompile::cmd_decompile(
             &matches.subcommand_matches("decompile").unwrap(),
         ),
+        "assemble" => commands::assemble::cmd_assemble(
+            &matches.subcommand_matches("assemble").unwrap(),
+        ),
+        #[cfg(feature = "ncurses")]
         "exec" => commands::exec::cmd_exec(
             &matches.subcommand_matches("exec").unwrap(),
         ),
+        "run" => commands::run::cmd_run(
+            &matches.subcommand_matches("run").unwrap(),
+        ),
         other => panic!("Invalid subcommand {}", other),
     }
 }
+
+#[cfg(feature = "ncurses")]
+fn exec_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("exec")
+        .about("Run the program with an ncurses interface")
+        .arg(Arg::with_name("input")
+            .short("i")
+            .value_name("INPUT")
+            .help("The file to be decompiled. If - then stdin is read.")
+            .required(true))
+        .arg(Arg::with_name("font")
+            .long("font")
+            .value_name("FONT")
+            .help("The hex font: default, vip, dream6800, eti660 or the path to an 80 byte file."))
+        .arg(Arg::with_name("ipf")
+            .long("ipf")
+            .value_name("N")
+            .help("Instructions executed per 60 Hz frame (default 10)"))
+        .arg(Arg::with_name("seed")
+            .long("seed")
+            .value_name("SEED")
+            .help("Seed for the RAND instruction, for reproducible runs"))
+        .arg(Arg::with_name("profile")
+            .long("profile")
+            .value_name("PROFILE")
+            .possible_values(&["vip", "chip48", "schip", "xochip"])
+            .help("Interpreter quirks to emulate (default vip)"))
+        .arg(Arg::with_name("load-state")
+            .long("load-state")
+            .value_name("FILE")
+            .help("Resume from a save state. F5/F9 save/load this file (default INPUT.state)"))
+        .arg(Arg::with_name("rewind")
+            .long("rewind")
+            .value_name("SECONDS")
+            .help("Seconds of history to keep for rewinding by holding r (default 10, 0 disables)"))
+        .arg(Arg::with_name("debug")
+            .long("debug")
+            .help("Start paused in the debugger: p continue/pause, n step, o step over, u step out, k toggle breakpoint"))
+        .arg(Arg::with_name("break")
+            .long("break")
+            .value_name("ADDR")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Pause at this hex address, can be given more than once (enables the debugger)"))
+        .arg(Arg::with_name("watch")
+            .long("watch")
+            .value_name("RANGE")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Pause after memory at ADDR or START-END is accessed, :r or :w for only reads or writes"))
+        .arg(Arg::with_name("break-if")
+            .long("break-if")
+            .value_name("CONDITION")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Pause when a condition such as 'V3 == 0x10' or 'I > 0xE00' becomes true"))
+        .arg(Arg::with_name("break-on")
+            .long("break-on")
+            .value_name("MNEMONIC")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Pause before every instruction of this kind, e.g. DRAW or RAND"))
+        .arg(Arg::with_name("gdb")
+            .long("gdb")
+            .value_name("PORT")
+            .help("Wait for a GDB remote protocol client on this local TCP port and let it control execution"))
+        .args(&instrument_args())
+}
+
+/// The options of the tools in `Instrumentation`, shared by `run` and `exec`.
+fn instrument_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("trace")
+            .long("trace")
+            .value_name("FILE")
+            .help("Write a JSON line per executed instruction to FILE"),
+        Arg::with_name("coverage")
+            .long("coverage")
+            .value_name("FILE")
+            .help("Write the decompile listing with execution counts to FILE"),
+        Arg::with_name("lcov")
+            .long("lcov")
+            .value_name("FILE")
+            .help("Write line and skip coverage of the --coverage listing as an LCOV tracefile"),
+        Arg::with_name("hotspots")
+            .long("hotspots")
+            .help("Print the most executed addresses, opcodes and subroutines at exit"),
+        Arg::with_name("hotspots-folded")
+            .long("hotspots-folded")
+            .value_name("FILE")
+            .help("Write instructions per call stack to FILE in the folded format of flamegraph.pl"),
+    ]
+}
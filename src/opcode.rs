@@ -2,6 +2,7 @@ use register::*;
 
 #[derive(Debug)]
 #[derive(PartialEq)]
+/// A decoded instruction, named by its assembler mnemonic.
 pub enum Opcode {
     CLS,
     RET,
@@ -37,13 +38,37 @@ pub enum Opcode {
     BCD(VReg),
     RDUMP(VReg),
     RLOAD(VReg),
+    // SUPER-CHIP 1.1
+    SCD(u8),
+    SCR,
+    SCL,
+    EXIT,
+    LOW,
+    HIGH,
+    XSPRITE(VReg),
+    SRPL(VReg),
+    LRPL(VReg),
+    // XO-CHIP
+    LONGI(u16),
+    SAVER(VReg, VReg),
+    LOADR(VReg, VReg),
+    PLANE(u8),
+    AUDIO,
+    PITCH(VReg),
 }
 
 impl Opcode {
+    /// Decodes a single instruction word, `None` if it isn't a known instruction.
     pub fn new(cmd: u16) -> Option<Opcode> {
         match cmd {
             0x00e0 => Some(Opcode::CLS),
             0x00ee => Some(Opcode::RET),
+            n @ 0x00c0 ... 0x00cf => Some(Opcode::SCD((n & 0x000f) as u8)),
+            0x00fb => Some(Opcode::SCR),
+            0x00fc => Some(Opcode::SCL),
+            0x00fd => Some(Opcode::EXIT),
+            0x00fe => Some(Opcode::LOW),
+            0x00ff => Some(Opcode::HIGH),
             n @ 0x1000 ... 0x1fff => Some(Opcode::JMP(n & 0x0fff)),
             n @ 0x2000 ... 0x2fff => Some(Opcode::CALL(n & 0x0fff)),
             n @ 0x3000 ... 0x3fff => Some(Opcode::SKIPEQ(
@@ -58,6 +83,14 @@ impl Opcode {
                 from_int(get_x(n)).unwrap(),
                 from_int(get_y(n)).unwrap(),
             )),
+            n @ 0x5000 ... 0x5fff if n & 0x000f == 2 => Some(Opcode::SAVER(
+                from_int(get_x(n)).unwrap(),
+                from_int(get_y(n)).unwrap(),
+            )),
+            n @ 0x5000 ... 0x5fff if n & 0x000f == 3 => Some(Opcode::LOADR(
+                from_int(get_x(n)).unwrap(),
+                from_int(get_y(n)).unwrap(),
+            )),
             n @ 0x6000 ... 0x6fff => Some(
                 Opcode::MOV(from_int(get_x(n)).unwrap(), (n & 0x00ff) as u8),
             ),
@@ -148,49 +181,96 @@ impl Opcode {
             n @ 0xf000 ... 0xffff if n & 0x00ff == 0x65 => Some(
                 Opcode::RLOAD(from_int(get_x(n)).unwrap()),
             ),
+            n @ 0xf000 ... 0xffff if n & 0x00ff == 0x30 => Some(Opcode::XSPRITE(
+                from_int(get_x(n)).unwrap(),
+            )),
+            n @ 0xf000 ... 0xffff if n & 0x00ff == 0x75 => Some(
+                Opcode::SRPL(from_int(get_x(n)).unwrap()),
+            ),
+            n @ 0xf000 ... 0xffff if n & 0x00ff == 0x85 => Some(
+                Opcode::LRPL(from_int(get_x(n)).unwrap()),
+            ),
+            n @ 0xf000 ... 0xffff if n & 0x00ff == 0x01 => Some(Opcode::PLANE(get_x(n))),
+            0xf002 => Some(Opcode::AUDIO),
+            n @ 0xf000 ... 0xffff if n & 0x00ff == 0x3a => Some(
+                Opcode::PITCH(from_int(get_x(n)).unwrap()),
+            ),
             _ => None,
         }
     }
 
-    //    pub fn to_asm_code(&self) -> &str {
-    //        match *self {
-    //            Opcode::CLS => "CLS",
-    //            Opcode::RET => "RET",
-    //            Opcode::JMP(_) => "JMP",
-    //            Opcode::CALL(_) => "CALL",
-    //            Opcode::SKIPEQ(_, _) => "SKIPEQ",
-    //            Opcode::SKIPNEQ(_, _) => "SKIPNEQ",
-    //            Opcode::SKIPREQ(_, _) => "SKIPREQ",
-    //            Opcode::MOV(_, _) => "MOV",
-    //            Opcode::ADD(_, _) => "ADD",
-    //            Opcode::MOVR(_, _) => "MOVR",
-    //            Opcode::OR(_, _) => "OR",
-    //            Opcode::AND(_, _) => "AND",
-    //            Opcode::XOR(_, _) => "XOR",
-    //            Opcode::ADDR(_, _) => "ADDR",
-    //            Opcode::SUBR(_, _) => "SUBR",
-    //            Opcode::SR(_, _) => "SR",
-    //            Opcode::RSUBR(_, _) => "RSUBR",
-    //            Opcode::SL(_, _) => "SL",
-    //            Opcode::SKIPRNEQ(_, _) => "SKIPRNEQ",
-    //            Opcode::SI(_) => "SI",
-    //            Opcode::JMPR(_) => "JMPR",
-    //            Opcode::RAND(_, _) => "RAND",
-    //            Opcode::DRAW(_, _, _) => "DRAW",
-    //            Opcode::SKIPKEQ(_) => "SKIPKEQ",
-    //            Opcode::SKIPKNEQ(_) => "SKIPKNEQ",
-    //            Opcode::GDELAY(_) => "GDELAY",
-    //            Opcode::GKEY(_) => "GKEY",
-    //            Opcode::SDELAY(_) => "SDELAY",
-    //            Opcode::SSND(_) => "SSND",
-    //            Opcode::ADDI(_) => "ADDI",
-    //            Opcode::SPRITE(_) => "SPRITE",
-    //            Opcode::BCD(_) => "BCD",
-    //            Opcode::RDUMP(_) => "RDUMP",
-    //            Opcode::RLOAD(_) => "RLOAD",
-    //        }
-    //    }
+    /// Like `new`, but also decodes `F000 NNNN`, which takes the following word as its operand.
+    pub fn decode(cmd: u16, next: Option<u16>) -> Option<Opcode> {
+        match cmd {
+            0xf000 => next.map(Opcode::LONGI),
+            _ => Opcode::new(cmd),
+        }
+    }
+
+    /// Size of the encoded instruction in bytes.
+    pub fn size(&self) -> u16 {
+        match *self {
+            Opcode::LONGI(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// The mnemonic alone, e.g. "DRAW" for any `DRAW` instruction.
+    pub fn to_asm_code(&self) -> &'static str {
+        match *self {
+            Opcode::CLS => "CLS",
+            Opcode::RET => "RET",
+            Opcode::JMP(_) => "JMP",
+            Opcode::CALL(_) => "CALL",
+            Opcode::SKIPEQ(_, _) => "SKIPEQ",
+            Opcode::SKIPNEQ(_, _) => "SKIPNEQ",
+            Opcode::SKIPREQ(_, _) => "SKIPREQ",
+            Opcode::MOV(_, _) => "MOV",
+            Opcode::ADD(_, _) => "ADD",
+            Opcode::MOVR(_, _) => "MOVR",
+            Opcode::OR(_, _) => "OR",
+            Opcode::AND(_, _) => "AND",
+            Opcode::XOR(_, _) => "XOR",
+            Opcode::ADDR(_, _) => "ADDR",
+            Opcode::SUBR(_, _) => "SUBR",
+            Opcode::SR(_, _) => "SR",
+            Opcode::RSUBR(_, _) => "RSUBR",
+            Opcode::SL(_, _) => "SL",
+            Opcode::SKIPRNEQ(_, _) => "SKIPRNEQ",
+            Opcode::SI(_) => "SI",
+            Opcode::JMPR(_) => "JMPR",
+            Opcode::RAND(_, _) => "RAND",
+            Opcode::DRAW(_, _, _) => "DRAW",
+            Opcode::SKIPKEQ(_) => "SKIPKEQ",
+            Opcode::SKIPKNEQ(_) => "SKIPKNEQ",
+            Opcode::GDELAY(_) => "GDELAY",
+            Opcode::GKEY(_) => "GKEY",
+            Opcode::SDELAY(_) => "SDELAY",
+            Opcode::SSND(_) => "SSND",
+            Opcode::ADDI(_) => "ADDI",
+            Opcode::SPRITE(_) => "SPRITE",
+            Opcode::BCD(_) => "BCD",
+            Opcode::RDUMP(_) => "RDUMP",
+            Opcode::RLOAD(_) => "RLOAD",
+            Opcode::SCD(_) => "SCD",
+            Opcode::SCR => "SCR",
+            Opcode::SCL => "SCL",
+            Opcode::EXIT => "EXIT",
+            Opcode::LOW => "LOW",
+            Opcode::HIGH => "HIGH",
+            Opcode::XSPRITE(_) => "XSPRITE",
+            Opcode::SRPL(_) => "SRPL",
+            Opcode::LRPL(_) => "LRPL",
+            Opcode::LONGI(_) => "LONGI",
+            Opcode::SAVER(_, _) => "SAVER",
+            Opcode::LOADR(_, _) => "LOADR",
+            Opcode::PLANE(_) => "PLANE",
+            Opcode::AUDIO => "AUDIO",
+            Opcode::PITCH(_) => "PITCH",
+        }
+    }
 
+    /// The instruction in the syntax of `decompile` and `assemble`, e.g. "MOV V0, #$2A".
     pub fn to_asm(&self) -> String {
         match *self {
             Opcode::CLS => format!("CLS"),
@@ -227,9 +307,25 @@ impl Opcode {
             Opcode::BCD(ref x) => format!("BCD {}", x),
             Opcode::RDUMP(ref x) => format!("RDUMP {}", x),
             Opcode::RLOAD(ref x) => format!("RLOAD {}", x),
+            Opcode::SCD(ref n) => format!("SCD #${:X}", n),
+            Opcode::SCR => format!("SCR"),
+            Opcode::SCL => format!("SCL"),
+            Opcode::EXIT => format!("EXIT"),
+            Opcode::LOW => format!("LOW"),
+            Opcode::HIGH => format!("HIGH"),
+            Opcode::XSPRITE(ref x) => format!("XSPRITE {}", x),
+            Opcode::SRPL(ref x) => format!("SRPL {}", x),
+            Opcode::LRPL(ref x) => format!("LRPL {}", x),
+            Opcode::LONGI(ref i) => format!("LONGI ${:X}", i),
+            Opcode::SAVER(ref x, ref y) => format!("SAVER {}, {}", x, y),
+            Opcode::LOADR(ref x, ref y) => format!("LOADR {}, {}", x, y),
+            Opcode::PLANE(ref n) => format!("PLANE #${:X}", n),
+            Opcode::AUDIO => format!("AUDIO"),
+            Opcode::PITCH(ref x) => format!("PITCH {}", x),
         }
     }
 
+    /// The first word of the instruction, see `to_bytes` for the full encoding.
     pub fn to_bin(&self) -> u16 {
         match *self {
             Opcode::CLS => 0x00e0,
@@ -290,7 +386,37 @@ impl Opcode {
             Opcode::BCD(ref x) => 0xf033 | ((x.v as u16) << 8) as u16,
             Opcode::RDUMP(ref x) => 0xf055 | ((x.v as u16) << 8) as u16,
             Opcode::RLOAD(ref x) => 0xf065 | ((x.v as u16) << 8) as u16,
+            Opcode::SCD(ref n) => 0x00c0 | *n as u16,
+            Opcode::SCR => 0x00fb,
+            Opcode::SCL => 0x00fc,
+            Opcode::EXIT => 0x00fd,
+            Opcode::LOW => 0x00fe,
+            Opcode::HIGH => 0x00ff,
+            Opcode::XSPRITE(ref x) => 0xf030 | ((x.v as u16) << 8) as u16,
+            Opcode::SRPL(ref x) => 0xf075 | ((x.v as u16) << 8) as u16,
+            Opcode::LRPL(ref x) => 0xf085 | ((x.v as u16) << 8) as u16,
+            Opcode::LONGI(_) => 0xf000,
+            Opcode::SAVER(ref x, ref y) => {
+                0x5002 | ((x.v as u16) << 8) as u16 | ((y.v as u16) << 4) as u16
+            }
+            Opcode::LOADR(ref x, ref y) => {
+                0x5003 | ((x.v as u16) << 8) as u16 | ((y.v as u16) << 4) as u16
+            }
+            Opcode::PLANE(ref n) => 0xf001 | ((*n as u16) << 8) as u16,
+            Opcode::AUDIO => 0xf002,
+            Opcode::PITCH(ref x) => 0xf03a | ((x.v as u16) << 8) as u16,
+        }
+    }
+
+    /// The full big-endian encoding, 4 bytes for `LONGI` and 2 for the rest.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let bin = self.to_bin();
+        let mut bytes = vec![(bin >> 8) as u8, (bin & 0xff) as u8];
+        if let Opcode::LONGI(n) = *self {
+            bytes.push((n >> 8) as u8);
+            bytes.push((n & 0xff) as u8);
         }
+        bytes
     }
 }
 
@@ -721,6 +847,90 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_new_schip() {
+        assert_eq!(Some(Opcode::SCD(0x0)), Opcode::new(0x00c0));
+        assert_eq!(Some(Opcode::SCD(0xa)), Opcode::new(0x00ca));
+        assert_eq!(Some(Opcode::SCD(0xf)), Opcode::new(0x00cf));
+        assert_eq!(None, Opcode::new(0x00d0));
+
+        assert_eq!(Some(Opcode::SCR), Opcode::new(0x00fb));
+        assert_eq!(Some(Opcode::SCL), Opcode::new(0x00fc));
+        assert_eq!(Some(Opcode::EXIT), Opcode::new(0x00fd));
+        assert_eq!(Some(Opcode::LOW), Opcode::new(0x00fe));
+        assert_eq!(Some(Opcode::HIGH), Opcode::new(0x00ff));
+
+        assert_eq!(
+            Some(Opcode::XSPRITE(from_int(0x0).unwrap())),
+            Opcode::new(0xf030)
+        );
+        assert_eq!(
+            Some(Opcode::XSPRITE(from_int(0xc).unwrap())),
+            Opcode::new(0xfc30)
+        );
+
+        assert_eq!(
+            Some(Opcode::SRPL(from_int(0x0).unwrap())),
+            Opcode::new(0xf075)
+        );
+        assert_eq!(
+            Some(Opcode::SRPL(from_int(0x7).unwrap())),
+            Opcode::new(0xf775)
+        );
+
+        assert_eq!(
+            Some(Opcode::LRPL(from_int(0x0).unwrap())),
+            Opcode::new(0xf085)
+        );
+        assert_eq!(
+            Some(Opcode::LRPL(from_int(0x7).unwrap())),
+            Opcode::new(0xf785)
+        );
+    }
+
+    #[test]
+    fn test_new_xochip() {
+        assert_eq!(None, Opcode::new(0xf000));
+        assert_eq!(None, Opcode::decode(0xf000, None));
+        assert_eq!(Some(Opcode::LONGI(0xabcd)), Opcode::decode(0xf000, Some(0xabcd)));
+        assert_eq!(Some(Opcode::CLS), Opcode::decode(0x00e0, Some(0xabcd)));
+
+        assert_eq!(
+            Some(Opcode::SAVER(from_int(0x1).unwrap(), from_int(0xe).unwrap())),
+            Opcode::new(0x51e2)
+        );
+        assert_eq!(
+            Some(Opcode::LOADR(from_int(0xe).unwrap(), from_int(0x1).unwrap())),
+            Opcode::new(0x5e13)
+        );
+
+        assert_eq!(Some(Opcode::PLANE(0x0)), Opcode::new(0xf001));
+        assert_eq!(Some(Opcode::PLANE(0x3)), Opcode::new(0xf301));
+
+        assert_eq!(Some(Opcode::AUDIO), Opcode::new(0xf002));
+        assert_eq!(None, Opcode::new(0xf102));
+
+        assert_eq!(
+            Some(Opcode::PITCH(from_int(0x5).unwrap())),
+            Opcode::new(0xf53a)
+        );
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(2, Opcode::CLS.size());
+        assert_eq!(4, Opcode::LONGI(0x1234).size());
+        assert_eq!(vec![0x00, 0xe0], Opcode::CLS.to_bytes());
+        assert_eq!(vec![0xf0, 0x00, 0x12, 0x34], Opcode::LONGI(0x1234).to_bytes());
+    }
+
+    #[test]
+    fn test_to_asm_code() {
+        assert_eq!("DRAW", Opcode::new(0xd125).unwrap().to_asm_code());
+        assert_eq!("RAND", Opcode::new(0xc3ff).unwrap().to_asm_code());
+        assert_eq!("LONGI", Opcode::decode(0xf000, Some(0x1234)).unwrap().to_asm_code());
+    }
+
     #[test]
     fn test_to_asm() {
         assert_eq!("CLS".to_string(), Opcode::new(0x00e0).unwrap().to_asm());
@@ -871,6 +1081,45 @@ mod tests {
             "RLOAD VC".to_string(),
             Opcode::new(0xfc65).unwrap().to_asm()
         );
+
+        assert_eq!("SCD #$A".to_string(), Opcode::new(0x00ca).unwrap().to_asm());
+
+        assert_eq!("SCR".to_string(), Opcode::new(0x00fb).unwrap().to_asm());
+
+        assert_eq!("SCL".to_string(), Opcode::new(0x00fc).unwrap().to_asm());
+
+        assert_eq!("EXIT".to_string(), Opcode::new(0x00fd).unwrap().to_asm());
+
+        assert_eq!("LOW".to_string(), Opcode::new(0x00fe).unwrap().to_asm());
+
+        assert_eq!("HIGH".to_string(), Opcode::new(0x00ff).unwrap().to_asm());
+
+        assert_eq!(
+            "XSPRITE VC".to_string(),
+            Opcode::new(0xfc30).unwrap().to_asm()
+        );
+
+        assert_eq!("SRPL VC".to_string(), Opcode::new(0xfc75).unwrap().to_asm());
+
+        assert_eq!("LRPL VC".to_string(), Opcode::new(0xfc85).unwrap().to_asm());
+
+        assert_eq!("LONGI $ABCD".to_string(), Opcode::LONGI(0xabcd).to_asm());
+
+        assert_eq!(
+            "SAVER V1, VE".to_string(),
+            Opcode::new(0x51e2).unwrap().to_asm()
+        );
+
+        assert_eq!(
+            "LOADR V1, VE".to_string(),
+            Opcode::new(0x51e3).unwrap().to_asm()
+        );
+
+        assert_eq!("PLANE #$3".to_string(), Opcode::new(0xf301).unwrap().to_asm());
+
+        assert_eq!("AUDIO".to_string(), Opcode::new(0xf002).unwrap().to_asm());
+
+        assert_eq!("PITCH V5".to_string(), Opcode::new(0xf53a).unwrap().to_asm());
     }
 
     #[test]
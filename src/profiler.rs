@@ -0,0 +1,235 @@
+use state::Chip8State;
+use opcode::Opcode;
+use std::collections::BTreeMap;
+
+/// Lines in each table of the report.
+const REPORT_ROWS: usize = 20;
+
+/// Instruction counts per 60 Hz frame, or per frame that drew something.
+struct FrameStats {
+    frames: u64,
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl FrameStats {
+    fn new() -> FrameStats {
+        FrameStats { frames: 0, total: 0, min: u64::max_value(), max: 0 }
+    }
+
+    fn add(&mut self, instructions: u64) {
+        self.frames += 1;
+        self.total += instructions;
+        self.min = self.min.min(instructions);
+        self.max = self.max.max(instructions);
+    }
+
+    fn summary(&self) -> String {
+        if self.frames == 0 {
+            return "none".to_string();
+        }
+        format!("min {}, avg {}, max {}", self.min, self.total / self.frames, self.max)
+    }
+}
+
+/// Counts executions per address, per opcode kind and per call stack while a ROM runs.
+///
+/// Like `Tracer`, `before_instruction` is called ahead of every instruction, and `end_frame`
+/// after every 60 Hz frame.
+pub struct Profiler {
+    per_pc: BTreeMap<u16, u64>,
+    per_opcode: BTreeMap<&'static str, u64>,
+    /// Instructions by the entry points of the active subroutines, outermost first.
+    stacks: BTreeMap<Vec<u16>, u64>,
+    instructions: u64,
+    frame_instructions: u64,
+    frames: FrameStats,
+    drawn_instructions: u64,
+    drew: bool,
+    drawn_frames: FrameStats,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            per_pc: BTreeMap::new(),
+            per_opcode: BTreeMap::new(),
+            stacks: BTreeMap::new(),
+            instructions: 0,
+            frame_instructions: 0,
+            frames: FrameStats::new(),
+            drawn_instructions: 0,
+            drew: false,
+            drawn_frames: FrameStats::new(),
+        }
+    }
+
+    pub fn before_instruction(&mut self, state: &Chip8State) {
+        self.instructions += 1;
+        self.frame_instructions += 1;
+        *self.per_pc.entry(state.pc).or_insert(0) += 1;
+        if let Some(opcode) = state.get_next_opcode() {
+            *self.per_opcode.entry(opcode.to_asm_code()).or_insert(0) += 1;
+            self.drew |= match opcode {
+                Opcode::CLS | Opcode::DRAW(_, _, _) | Opcode::SCD(_) | Opcode::SCR | Opcode::SCL => true,
+                _ => false,
+            };
+        }
+        // The stack holds the addresses of the CALLs, the routines are their targets
+        let routines: Vec<u16> = state
+            .call_stack()
+            .iter()
+            .map(|&addr| match state.opcode_at(addr) {
+                Some(Opcode::CALL(target)) => target,
+                _ => addr,
+            })
+            .collect();
+        *self.stacks.entry(routines).or_insert(0) += 1;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frames.add(self.frame_instructions);
+        self.drawn_instructions += self.frame_instructions;
+        self.frame_instructions = 0;
+        if self.drew {
+            self.drawn_frames.add(self.drawn_instructions);
+            self.drawn_instructions = 0;
+            self.drew = false;
+        }
+    }
+
+    /// Hot spots, opcode kinds and subroutines sorted by instructions executed, most first.
+    pub fn report(&self, state: &Chip8State) -> Vec<String> {
+        let mut lines = vec![
+            format!("Instructions: {} in {} frames", self.instructions, self.frames.frames),
+            format!("Instructions per frame: {}", self.frames.summary()),
+            format!("Instructions per drawn frame: {}", self.drawn_frames.summary()),
+            String::new(),
+            format!("{:>10} {:>6}  Hot spot", "Count", "%"),
+        ];
+        for (&pc, &count) in sorted(&self.per_pc).iter().take(REPORT_ROWS) {
+            let asm = state.opcode_at(pc).map_or("[UNDEFINED]".to_string(), |oc| oc.to_asm());
+            lines.push(format!("{:>10} {:>6}  {:04X}: {}", count, self.percent(count), pc, asm));
+        }
+        lines.push(String::new());
+        lines.push(format!("{:>10} {:>6}  Opcode", "Count", "%"));
+        for (&name, &count) in sorted(&self.per_opcode).iter().take(REPORT_ROWS) {
+            lines.push(format!("{:>10} {:>6}  {}", count, self.percent(count), name));
+        }
+        lines.push(String::new());
+        lines.push(format!("{:>10} {:>10} {:>6}  Subroutine", "Self", "Total", "%"));
+        let mut routines: BTreeMap<Option<u16>, (u64, u64)> = BTreeMap::new();
+        for (stack, &count) in self.stacks.iter() {
+            routines.entry(stack.last().cloned()).or_insert((0, 0)).0 += count;
+            // Recursive routines only count once towards their total
+            let mut seen: Vec<Option<u16>> = vec![None];
+            seen.extend(stack.iter().map(|&r| Some(r)));
+            seen.sort();
+            seen.dedup();
+            for routine in seen {
+                routines.entry(routine).or_insert((0, 0)).1 += count;
+            }
+        }
+        let mut routines: Vec<(Option<u16>, (u64, u64))> = routines.into_iter().collect();
+        routines.sort_by(|a, b| (b.1).1.cmp(&(a.1).1).then(a.0.cmp(&b.0)));
+        for &(routine, (own, total)) in routines.iter().take(REPORT_ROWS) {
+            lines.push(format!("{:>10} {:>10} {:>6}  {}", own, total, self.percent(total), name(routine)));
+        }
+        lines
+    }
+
+    /// Call stacks in the folded format read by flamegraph.pl and similar tools.
+    pub fn folded(&self) -> String {
+        let mut out = String::new();
+        for (stack, count) in self.stacks.iter() {
+            let mut names = vec![name(None)];
+            names.extend(stack.iter().map(|&r| name(Some(r))));
+            out.push_str(&format!("{} {}\n", names.join(";"), count));
+        }
+        out
+    }
+
+    fn percent(&self, count: u64) -> String {
+        format!("{:.1}", count as f64 * 100.0 / self.instructions.max(1) as f64)
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new()
+    }
+}
+
+/// Entries sorted by count, most first, ties in key order.
+fn sorted<K: Ord + Copy>(counts: &BTreeMap<K, u64>) -> Vec<(&K, &u64)> {
+    let mut entries: Vec<(&K, &u64)> = counts.iter().collect();
+    entries.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    entries
+}
+
+/// Subroutines are named like the labels of `decompile --flow`, code outside of any is "main".
+fn name(routine: Option<u16>) -> String {
+    match routine {
+        Some(addr) => format!("L{:03X}", addr),
+        None => "main".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use io::NullIo;
+    use program::Chip8Program;
+
+    fn run() -> (Profiler, Chip8State) {
+        let mut state = Chip8State::new();
+        // 200: CALL $206, 202: CLS, 204: JMP $200, 206: CALL $20A, 208: RET, 20A: RET
+        state.load_program(&Chip8Program::new(&[
+            0x22, 0x06, 0x00, 0xe0, 0x12, 0x00, 0x22, 0x0a, 0x00, 0xee, 0x00, 0xee,
+        ])).unwrap();
+        let mut profiler = Profiler::new();
+        for frame in 0..4 {
+            for _ in 0..(frame + 2) * 3 {
+                profiler.before_instruction(&state);
+                state.exec_step(&mut NullIo).unwrap();
+            }
+            profiler.end_frame();
+        }
+        (profiler, state)
+    }
+
+    #[test]
+    fn test_report() {
+        let (profiler, state) = run();
+        let report = profiler.report(&state);
+        // 6 instructions per loop, 42 in all
+        assert_eq!(
+            vec![
+                "Instructions: 42 in 4 frames",
+                "Instructions per frame: min 6, avg 10, max 15",
+                "Instructions per drawn frame: min 6, avg 10, max 15",
+                "",
+                "     Count      %  Hot spot",
+                "         7   16.7  0200: CALL $206",
+            ],
+            &report[..6]
+        );
+        assert!(report.contains(&"        14   33.3  RET".to_string()));
+        let subroutines = report.iter().position(|l| l.ends_with("Subroutine")).unwrap();
+        assert_eq!(
+            vec![
+                "        21         42  100.0  main",
+                "        14         21   50.0  L206",
+                "         7          7   16.7  L20A",
+            ],
+            &report[subroutines + 1..]
+        );
+    }
+
+    #[test]
+    fn test_folded() {
+        let (profiler, _) = run();
+        assert_eq!("main 21\nmain;L206 14\nmain;L206;L20A 7\n", profiler.folded());
+    }
+}
@@ -3,11 +3,13 @@ use std::vec::Vec;
 
 #[derive(Debug)]
 #[derive(PartialEq)]
+/// A ROM as big-endian instruction words.
 pub struct Chip8Program {
     pub instructions: Vec<u16>,
 }
 
 impl Chip8Program {
+    /// Splits a ROM image into words. A trailing odd byte is dropped.
     pub fn new(source: &[u8]) -> Chip8Program {
         let mut instructions = vec![];
         if source.len() % 2 == 1 {
@@ -20,6 +22,7 @@ impl Chip8Program {
         Chip8Program { instructions }
     }
 
+    /// Reads a whole ROM from `source`.
     pub fn from(mut source: Box<io::Read>) -> Result<Chip8Program, io::Error> {
         let mut buf = Vec::new();
         let size = source.read_to_end(&mut buf);
@@ -29,6 +32,16 @@ impl Chip8Program {
         assert_eq!(size.unwrap(), buf.len());
         Ok(Chip8Program::new(&buf[..]))
     }
+
+    /// The ROM image, as loaded at 0x200.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.instructions.len() * 2);
+        for instruction in self.instructions.iter() {
+            bytes.push((instruction >> 8) as u8);
+            bytes.push((instruction & 0xff) as u8);
+        }
+        bytes
+    }
 }
 
 #[cfg(test)]
@@ -70,4 +83,14 @@ mod tests {
             Chip8Program::new(&[0x12, 0x34, 0x12, 0x42, 0xab, 0xcd, 0xff])
         );
     }
+
+    #[test]
+    fn test_to_bytes() {
+        assert_eq!(Vec::<u8>::new(), Chip8Program::new(&[]).to_bytes());
+        assert_eq!(vec![0x12, 0x34], Chip8Program::new(&[0x12, 0x34]).to_bytes());
+        assert_eq!(
+            vec![0x12, 0x34, 0xab, 0xcd],
+            Chip8Program::new(&[0x12, 0x34, 0xab, 0xcd, 0xff]).to_bytes()
+        );
+    }
 }
@@ -0,0 +1,106 @@
+/// Where `RDUMP`/`RLOAD` leave I.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum LoadStoreI {
+    /// I points past the last register touched, as on the COSMAC VIP.
+    PastLast,
+    /// I points at the last register touched, as on CHIP-48.
+    AtLast,
+    /// I is left unchanged, as on SUPER-CHIP 1.1.
+    Unchanged,
+}
+
+/// Behaviour that differs between CHIP-8 interpreters.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub struct Quirks {
+    /// `SR`/`SL` shift VY into VX instead of shifting VX in place.
+    pub shift_vy: bool,
+    /// Where `RDUMP`/`RLOAD` leave I.
+    pub load_store_i: LoadStoreI,
+    /// `JMPR` jumps to NNN + VX, X being the top nibble of NNN, instead of NNN + V0.
+    pub jump_vx: bool,
+    /// `OR`/`AND`/`XOR` reset VF to 0.
+    pub vf_reset: bool,
+    /// `DRAW` waits for the next frame, so at most one sprite is drawn per frame.
+    pub display_wait: bool,
+    /// Sprites are clipped at the screen edges instead of wrapping around.
+    pub clip_sprites: bool,
+}
+
+impl Quirks {
+    /// The original interpreter on the RCA COSMAC VIP.
+    pub fn cosmac_vip() -> Quirks {
+        Quirks {
+            shift_vy: true,
+            load_store_i: LoadStoreI::PastLast,
+            jump_vx: false,
+            vf_reset: true,
+            display_wait: true,
+            clip_sprites: true,
+        }
+    }
+
+    /// CHIP-48 on the HP-48 calculators.
+    pub fn chip48() -> Quirks {
+        Quirks {
+            shift_vy: false,
+            load_store_i: LoadStoreI::AtLast,
+            jump_vx: true,
+            vf_reset: false,
+            display_wait: false,
+            clip_sprites: true,
+        }
+    }
+
+    /// SUPER-CHIP 1.1.
+    pub fn schip() -> Quirks {
+        Quirks {
+            shift_vy: false,
+            load_store_i: LoadStoreI::Unchanged,
+            jump_vx: true,
+            vf_reset: false,
+            display_wait: false,
+            clip_sprites: true,
+        }
+    }
+
+    /// XO-CHIP, as implemented by Octo.
+    pub fn xochip() -> Quirks {
+        Quirks {
+            shift_vy: true,
+            load_store_i: LoadStoreI::PastLast,
+            jump_vx: false,
+            vf_reset: false,
+            display_wait: false,
+            clip_sprites: false,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match name {
+            "vip" => Some(Quirks::cosmac_vip()),
+            "chip48" => Some(Quirks::chip48()),
+            "schip" => Some(Quirks::schip()),
+            "xochip" => Some(Quirks::xochip()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Some(Quirks::cosmac_vip()), Quirks::from_name("vip"));
+        assert_eq!(Some(Quirks::chip48()), Quirks::from_name("chip48"));
+        assert_eq!(Some(Quirks::schip()), Quirks::from_name("schip"));
+        assert_eq!(Some(Quirks::xochip()), Quirks::from_name("xochip"));
+        assert_eq!(None, Quirks::from_name("chip-9"));
+        assert!(Quirks::chip48() != Quirks::schip());
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt;
 
 #[derive(Debug)]
 #[derive(PartialEq)]
+/// One of the sixteen 8-bit registers V0-VF.
 pub struct VReg {
     pub v: u8,
 }
@@ -12,6 +13,7 @@ impl fmt::Display for VReg {
     }
 }
 
+/// The register numbered `oc`, `None` above VF.
 pub fn from_int(oc: u8) -> Option<VReg> {
     if oc > 0xf {
         Option::None
@@ -20,10 +22,12 @@ pub fn from_int(oc: u8) -> Option<VReg> {
     }
 }
 
+/// The register in the `_X__` nibble of an instruction word.
 pub fn get_x(oc: u16) -> u8 {
     ((oc >> 8) & 0x0f) as u8
 }
 
+/// The register in the `__Y_` nibble of an instruction word.
 pub fn get_y(oc: u16) -> u8 {
     ((oc >> 4) & 0x0f) as u8
 }
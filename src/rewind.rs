@@ -0,0 +1,145 @@
+use state::Chip8State;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Seconds of history kept by `exec` unless told otherwise.
+pub const DEFAULT_REWIND_SECONDS: u64 = 10;
+const PAGE_SIZE: usize = 256;
+
+/// A save state cut into pages. Pages equal to the previous snapshot's are shared with it.
+struct Snapshot {
+    pages: Vec<Rc<Vec<u8>>>,
+}
+
+impl Snapshot {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.pages.len() * PAGE_SIZE);
+        for page in self.pages.iter() {
+            buf.extend_from_slice(page);
+        }
+        buf
+    }
+}
+
+/// A ring buffer of the most recent per-frame states.
+///
+/// Frames mostly touch a few bytes of RAM and registers, so sharing unchanged pages keeps a
+/// snapshot down to little more than its page table.
+pub struct Rewind {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+}
+
+impl Rewind {
+    /// Keeps up to `capacity` snapshots, dropping the oldest first.
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind { snapshots: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Stores the current state as the newest snapshot.
+    pub fn record(&mut self, state: &Chip8State) {
+        if self.capacity == 0 {
+            return;
+        }
+        let buf = state.save_state();
+        let pages = {
+            let previous = self.snapshots.back().map(|s| &s.pages[..]).unwrap_or(&[]);
+            buf.chunks(PAGE_SIZE)
+                .enumerate()
+                .map(|(n, chunk)| match previous.get(n) {
+                    Some(page) if &page[..] == chunk => page.clone(),
+                    _ => Rc::new(chunk.to_vec()),
+                })
+                .collect()
+        };
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot { pages });
+    }
+
+    /// Restores the newest snapshot and drops it, so repeated calls walk back one frame at a time.
+    /// Returns false once the history is exhausted.
+    pub fn step_back(&mut self, state: &mut Chip8State) -> bool {
+        match self.snapshots.pop_back() {
+            Some(snapshot) => {
+                state.load_state(&snapshot.to_bytes()).expect("Rewind snapshot is always valid");
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::VirtualClock;
+    use io::NullIo;
+    use program::Chip8Program;
+
+    fn counting_state() -> Chip8State {
+        let mut state = Chip8State::new();
+        state.set_clock(Box::new(VirtualClock::new()));
+        // ADD V0, #$1 / JMP $200
+        state.load_program(&Chip8Program::new(&[0x70, 0x01, 0x12, 0x00])).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut state = counting_state();
+        let mut rewind = Rewind::new(10);
+        for _ in 0..3 {
+            rewind.record(&state);
+            state.exec_step(&mut NullIo).unwrap();
+            state.exec_step(&mut NullIo).unwrap();
+        }
+        assert_eq!(3, state.vregs[0x0]);
+        assert!(rewind.step_back(&mut state));
+        assert_eq!(2, state.vregs[0x0]);
+        assert!(rewind.step_back(&mut state));
+        assert!(rewind.step_back(&mut state));
+        assert_eq!(0, state.vregs[0x0]);
+        assert_eq!(0x200, state.pc);
+        assert!(!rewind.step_back(&mut state));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut state = counting_state();
+        let mut rewind = Rewind::new(2);
+        for _ in 0..5 {
+            rewind.record(&state);
+            state.exec_step(&mut NullIo).unwrap();
+        }
+        assert_eq!(2, rewind.len());
+        rewind.step_back(&mut state);
+        rewind.step_back(&mut state);
+        // Back at the state before the fourth instruction
+        assert_eq!(2, state.vregs[0x0]);
+        assert_eq!(0x202, state.pc);
+        assert!(!rewind.step_back(&mut state));
+
+        let mut disabled = Rewind::new(0);
+        disabled.record(&state);
+        assert_eq!(0, disabled.len());
+    }
+
+    #[test]
+    fn test_shared_pages() {
+        let mut state = counting_state();
+        let mut rewind = Rewind::new(10);
+        rewind.record(&state);
+        state.exec_step(&mut NullIo).unwrap();
+        rewind.record(&state);
+        let (a, b) = (&rewind.snapshots[0].pages, &rewind.snapshots[1].pages);
+        let shared = a.iter().zip(b.iter()).filter(|&(x, y)| Rc::ptr_eq(x, y)).count();
+        // Only the page holding the registers changed
+        assert_eq!(a.len() - 1, shared);
+    }
+}
@@ -0,0 +1,73 @@
+use rand;
+
+/// The xorshift64* generator behind `RAND`.
+///
+/// Implemented here rather than taken from `rand` so that a seed replays identically across builds.
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct Chip8Rng {
+    seed: u64,
+    state: u64,
+}
+
+impl Chip8Rng {
+    pub fn new(seed: u64) -> Chip8Rng {
+        // splitmix64 spreads the seed so small seeds still give a good, non-zero state
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        Chip8Rng { seed, state: if z == 0 { 1 } else { z } }
+    }
+
+    /// A generator with a random seed.
+    pub fn from_entropy() -> Chip8Rng {
+        Chip8Rng::new(rand::random())
+    }
+
+    /// Rebuilds a generator from `seed` and `state`, e.g. from a save state.
+    pub fn from_parts(seed: u64, state: u64) -> Chip8Rng {
+        Chip8Rng { seed, state: if state == 0 { 1 } else { state } }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545f4914f6cdd1d) >> 56) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed() {
+        let mut a = Chip8Rng::new(42);
+        let mut b = Chip8Rng::new(42);
+        let mut c = Chip8Rng::new(43);
+        let a_vals: Vec<u8> = (0..32).map(|_| a.next_u8()).collect();
+        let b_vals: Vec<u8> = (0..32).map(|_| b.next_u8()).collect();
+        let c_vals: Vec<u8> = (0..32).map(|_| c.next_u8()).collect();
+        assert_eq!(a_vals, b_vals);
+        assert_ne!(a_vals, c_vals);
+        assert_eq!(42, a.seed());
+    }
+
+    #[test]
+    fn test_zero_seed() {
+        let mut rng = Chip8Rng::new(0);
+        let vals: Vec<u8> = (0..32).map(|_| rng.next_u8()).collect();
+        assert!(vals.iter().any(|&v| v != 0));
+    }
+}
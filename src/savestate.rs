@@ -0,0 +1,185 @@
+use std::fmt;
+
+/// Every save state file starts with these bytes.
+pub const MAGIC: &[u8; 4] = b"C8SS";
+/// Bumped whenever the layout changes. Older versions are rejected rather than guessed at.
+pub const VERSION: u8 = 1;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum SaveStateError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    Invalid(&'static str),
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SaveStateError::BadMagic => write!(f, "Not a save state"),
+            SaveStateError::UnsupportedVersion(v) => {
+                write!(f, "Save state version {} is not supported (expected {})", v, VERSION)
+            }
+            SaveStateError::Truncated => write!(f, "Save state is truncated"),
+            SaveStateError::Invalid(what) => write!(f, "Save state has an invalid {}", what),
+        }
+    }
+}
+
+/// Builds the big-endian binary layout of a save state, starting with the header.
+pub struct SaveWriter {
+    buf: Vec<u8>,
+}
+
+impl SaveWriter {
+    pub fn new() -> SaveWriter {
+        let mut buf = MAGIC.to_vec();
+        buf.push(VERSION);
+        SaveWriter { buf }
+    }
+
+    pub fn u8(&mut self, n: u8) {
+        self.buf.push(n);
+    }
+
+    pub fn bool(&mut self, b: bool) {
+        self.buf.push(b as u8);
+    }
+
+    pub fn u16(&mut self, n: u16) {
+        self.buf.extend_from_slice(&[(n >> 8) as u8, n as u8]);
+    }
+
+    pub fn u32(&mut self, n: u32) {
+        self.u16((n >> 16) as u16);
+        self.u16(n as u16);
+    }
+
+    pub fn u64(&mut self, n: u64) {
+        self.u32((n >> 32) as u32);
+        self.u32(n as u32);
+    }
+
+    /// Writes a length-prefixed byte slice.
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+impl Default for SaveWriter {
+    fn default() -> SaveWriter {
+        SaveWriter::new()
+    }
+}
+
+/// Reads back what `SaveWriter` produced, checking the header first.
+pub struct SaveReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SaveReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Result<SaveReader<'a>, SaveStateError> {
+        if buf.len() < MAGIC.len() || &buf[..MAGIC.len()] != MAGIC {
+            return Err(SaveStateError::BadMagic);
+        }
+        let mut reader = SaveReader { buf, pos: MAGIC.len() };
+        match reader.u8()? {
+            VERSION => Ok(reader),
+            other => Err(SaveStateError::UnsupportedVersion(other)),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.pos + len > self.buf.len() {
+            return Err(SaveStateError::Truncated);
+        }
+        let slice = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::Invalid("flag")),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, SaveStateError> {
+        let b = self.take(2)?;
+        Ok(((b[0] as u16) << 8) | b[1] as u16)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(((self.u16()? as u32) << 16) | self.u16()? as u32)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(((self.u32()? as u64) << 32) | self.u32()? as u64)
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], SaveStateError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    /// Fails unless everything was read, catching layouts that drifted apart.
+    pub fn finish(self) -> Result<(), SaveStateError> {
+        if self.pos == self.buf.len() {
+            Ok(())
+        } else {
+            Err(SaveStateError::Invalid("length"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut w = SaveWriter::new();
+        w.u8(0x12);
+        w.bool(true);
+        w.u16(0x3456);
+        w.u32(0x789a_bcde);
+        w.u64(0x0123_4567_89ab_cdef);
+        w.bytes(&[1, 2, 3]);
+        let buf = w.finish();
+        assert_eq!(b"C8SS", &buf[..4]);
+
+        let mut r = SaveReader::new(&buf).unwrap();
+        assert_eq!(Ok(0x12), r.u8());
+        assert_eq!(Ok(true), r.bool());
+        assert_eq!(Ok(0x3456), r.u16());
+        assert_eq!(Ok(0x789a_bcde), r.u32());
+        assert_eq!(Ok(0x0123_4567_89ab_cdef), r.u64());
+        assert_eq!(Ok(&[1u8, 2, 3][..]), r.bytes());
+        assert_eq!(Ok(()), r.finish());
+    }
+
+    #[test]
+    fn test_header() {
+        assert_eq!(Some(SaveStateError::BadMagic), SaveReader::new(b"C8").err());
+        assert_eq!(Some(SaveStateError::BadMagic), SaveReader::new(b"XXXX\x01").err());
+        assert_eq!(
+            Some(SaveStateError::UnsupportedVersion(99)),
+            SaveReader::new(b"C8SS\x63").err()
+        );
+        let mut r = SaveReader::new(b"C8SS\x01\x00").unwrap();
+        assert_eq!(Err(SaveStateError::Truncated), r.u16());
+    }
+}
@@ -0,0 +1,128 @@
+use state::Chip8State;
+use io::Chip8Io;
+use fault::Chip8Fault;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_IPF: u32 = 10;
+
+/// Runs a `Chip8State` in 60 Hz frames of a fixed number of instructions.
+///
+/// The state should use a `VirtualClock`, which is ticked once at the end of every frame.
+pub struct Scheduler {
+    pub ipf: u32,
+    pub frames: u64,
+}
+
+impl Scheduler {
+    pub fn new(ipf: u32) -> Scheduler {
+        Scheduler { ipf, frames: 0 }
+    }
+
+    /// Executes `ipf` instructions, or fewer once the program exits, and then decrements the timers once.
+    /// A sound timer that ran out is reported to `io` straight away.
+    pub fn run_frame<T: Chip8Io>(&mut self, state: &mut Chip8State, io: &mut T) -> Result<(), Chip8Fault> {
+        self.run_frame_while(state, io, |_| true).map(|_| ())
+    }
+
+    /// Like `run_frame`, but checks `cond` before every instruction and stops mid-frame once it
+    /// is false. Returns whether the whole frame ran.
+    pub fn run_frame_while<T, F>(&mut self, state: &mut Chip8State, io: &mut T, mut cond: F) -> Result<bool, Chip8Fault>
+    where
+        T: Chip8Io,
+        F: FnMut(&Chip8State) -> bool,
+    {
+        for _ in 0..self.ipf {
+            if state.halted {
+                break;
+            }
+            if !cond(state) {
+                return Ok(false);
+            }
+            state.exec_step(io)?;
+        }
+        state.tick_60hz();
+        state.sync_sound(io);
+        self.frames += 1;
+        Ok(true)
+    }
+}
+
+/// Sleeps between frames to hold a real-time 60 Hz frame rate.
+pub struct FramePacer {
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new() -> FramePacer {
+        FramePacer { next_frame: Instant::now() }
+    }
+
+    /// Blocks until the next frame is due.
+    pub fn wait(&mut self) {
+        self.next_frame += Duration::from_micros(1_000_000 / 60);
+        let now = Instant::now();
+        if self.next_frame > now {
+            thread::sleep(self.next_frame - now);
+        } else {
+            // Running behind, don't try to catch up with a burst of frames
+            self.next_frame = now;
+        }
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> FramePacer {
+        FramePacer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::VirtualClock;
+    use fault::FaultKind;
+    use io::NullIo;
+    use program::Chip8Program;
+
+    #[test]
+    fn test_run_frame() {
+        let mut state = Chip8State::new();
+        state.set_clock(Box::new(VirtualClock::new()));
+        state.delay = 5;
+        // ADD V0, #$1 / JMP $200
+        state.load_program(&Chip8Program::new(&[0x70, 0x01, 0x12, 0x00])).unwrap();
+        let mut scheduler = Scheduler::new(10);
+        scheduler.run_frame(&mut state, &mut NullIo).unwrap();
+        assert_eq!(5, state.vregs[0x0]);
+        assert_eq!(4, state.delay);
+        scheduler.run_frame(&mut state, &mut NullIo).unwrap();
+        assert_eq!(10, state.vregs[0x0]);
+        assert_eq!(3, state.delay);
+        assert_eq!(2, scheduler.frames);
+    }
+
+    #[test]
+    fn test_run_frame_while() {
+        let mut state = Chip8State::new();
+        state.set_clock(Box::new(VirtualClock::new()));
+        state.load_program(&Chip8Program::new(&[0x70, 0x01, 0x12, 0x00])).unwrap();
+        let mut scheduler = Scheduler::new(10);
+        let complete = scheduler.run_frame_while(&mut state, &mut NullIo, |s| s.vregs[0x0] < 3).unwrap();
+        assert!(!complete);
+        assert_eq!(3, state.vregs[0x0]);
+        assert_eq!(0, scheduler.frames);
+    }
+
+    #[test]
+    fn test_run_frame_fault() {
+        let mut state = Chip8State::new();
+        state.set_clock(Box::new(VirtualClock::new()));
+        state.load_program(&Chip8Program::new(&[0x70, 0x01, 0xff, 0xff])).unwrap();
+        let mut scheduler = Scheduler::new(10);
+        let fault = scheduler.run_frame(&mut state, &mut NullIo).unwrap_err();
+        assert_eq!(FaultKind::InvalidOpcode, fault.kind);
+        assert_eq!(0x202, fault.pc);
+        assert_eq!(0, scheduler.frames);
+    }
+}
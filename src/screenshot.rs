@@ -0,0 +1,151 @@
+use framebuffer::Framebuffer;
+
+/// Characters for the four XO-CHIP colours in ASCII dumps.
+const ASCII_COLOURS: [char; 4] = ['.', '#', '+', '@'];
+/// Grey levels for the four XO-CHIP colours in PNG files.
+const PNG_COLOURS: [u8; 4] = [0x00, 0xff, 0xaa, 0x55];
+
+/// Renders the screen as one line of text per row.
+pub fn to_ascii(screen: &Framebuffer) -> String {
+    let mut out = String::with_capacity((screen.width() + 1) * screen.height());
+    for y in 0..screen.height() {
+        for x in 0..screen.width() {
+            out.push(ASCII_COLOURS[screen.planes(x, y) as usize]);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Encodes the screen as a plain (P1) PBM image, any lit plane counts as black.
+pub fn to_pbm(screen: &Framebuffer) -> Vec<u8> {
+    let mut out = format!("P1\n{} {}\n", screen.width(), screen.height());
+    for y in 0..screen.height() {
+        let row: Vec<&str> = (0..screen.width())
+            .map(|x| if screen.pixel(x, y) { "1" } else { "0" })
+            .collect();
+        out.push_str(&row.join(" "));
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+/// Encodes the screen as an 8-bit greyscale PNG.
+///
+/// The image data is stored uncompressed, which keeps the encoder small and the output stable.
+pub fn to_png(screen: &Framebuffer) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((screen.width() + 1) * screen.height());
+    for y in 0..screen.height() {
+        // Filter type 0 (none) for every scanline
+        raw.push(0);
+        for x in 0..screen.width() {
+            raw.push(PNG_COLOURS[screen.planes(x, y) as usize]);
+        }
+    }
+
+    let mut ihdr = vec![];
+    push_u32(&mut ihdr, screen.width() as u32);
+    push_u32(&mut ihdr, screen.height() as u32);
+    // Bit depth 8, greyscale, deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    let mut out = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    push_chunk(&mut out, b"IHDR", &ihdr);
+    push_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    push_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn push_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&[(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]);
+}
+
+fn push_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    push_u32(out, data.len() as u32);
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    push_u32(out, crc);
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&[len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]);
+        out.extend_from_slice(block);
+    }
+    push_u32(&mut out, adler32(data));
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen() -> Framebuffer {
+        let mut fb = Framebuffer::new(4, 2);
+        fb.xor_pixel(0, 0, 1);
+        fb.xor_pixel(3, 1, 2);
+        fb
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        assert_eq!("#...\n...+\n", to_ascii(&screen()));
+    }
+
+    #[test]
+    fn test_to_pbm() {
+        assert_eq!(b"P1\n4 2\n1 0 0 0\n0 0 0 1\n".to_vec(), to_pbm(&screen()));
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+        assert_eq!(0x11e6_0398, adler32(b"Wikipedia"));
+    }
+
+    #[test]
+    fn test_to_png() {
+        let png = to_png(&screen());
+        assert_eq!(&[0x89, b'P', b'N', b'G'], &png[..4]);
+        // IHDR with the screen size
+        assert_eq!(&b"IHDR"[..], &png[12..16]);
+        assert_eq!(&[0, 0, 0, 4, 0, 0, 0, 2], &png[16..24]);
+        // IEND always ends with the same CRC
+        assert_eq!(&[b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82], &png[png.len() - 8..]);
+        // The stored deflate block holds the filtered scanlines as-is
+        let idat = &png[33 + 8..];
+        assert_eq!(&[0x78, 0x01, 0x01, 10, 0, 0xf5, 0xff], &idat[..7]);
+        assert_eq!(&[0, 0xff, 0, 0, 0, 0, 0, 0, 0, 0xaa], &idat[7..17]);
+    }
+}
@@ -3,27 +3,83 @@ use std::fmt;
 use std::cmp;
 use opcode::Opcode;
 use register::*;
-use rand;
-use rand::Rng;
-use chrono::prelude::*;
+use rng::Chip8Rng;
+use quirks::{Quirks, LoadStoreI};
+use clock::{Clock, RealClock, VirtualClock};
+use font;
+use font::{FONT_SIZE, BIG_FONT_SIZE};
+use framebuffer::*;
+use io::Chip8Io;
+use fault::{Chip8Fault, FaultKind};
+use savestate::{SaveWriter, SaveReader, SaveStateError};
 
 #[cfg(test)]
 use test::Bencher;
 
 const FONT_START: u16 = 0x100;
-const DISP_START: u16 = 0xF00;
+const BIG_FONT_START: u16 = FONT_START + FONT_SIZE as u16;
 const STACK_START: u16 = 0xEA0;
+/// XO-CHIP extends the address space to the full 16 bits of `I`.
+const MEM_SIZE: usize = 0x10000;
+const PROGRAM_START: u16 = 0x200;
+/// Bytes from the program start to the end of memory.
+pub const MAX_PROGRAM_SIZE: usize = MEM_SIZE - PROGRAM_START as usize;
+/// The XO-CHIP default pitch, which plays the pattern buffer at 4000 Hz.
+const DEFAULT_PITCH: u8 = 64;
+
+/// A data read or write made by an instruction, see `Chip8State::last_accesses`.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub struct MemAccess {
+    pub addr: u16,
+    pub value: u8,
+    pub write: bool,
+    /// Made by `CALL` or `RET` to keep the return address, rather than by the ROM itself.
+    pub stack: bool,
+}
 
+/// The complete machine: registers, memory, timers, keys and screen.
 pub struct Chip8State {
+    /// V0-VF, VF doubles as the carry and collision flag.
     pub vregs: [u8; 16],
+    /// The address register used by memory and sprite instructions.
     pub i: u16,
+    /// Number of return addresses on the stack.
     pub sp: u8,
+    /// Address of the next instruction.
     pub pc: u16,
+    /// The delay timer, counting down at 60 Hz.
     pub delay: u8,
+    /// The sound timer, counting down at 60 Hz. The buzzer sounds while it's above 0.
     pub sound: u8,
-    timer_start: DateTime<Local>,
+    clock: Box<dyn Clock>,
+    /// Clock ticks the timers have been updated for.
     pub timer_updates: u64,
-    pub mem: [u8; 0x1000],
+    /// The 64 KiB address space, programs start at 0x200.
+    pub mem: Vec<u8>,
+    /// Which of the 16 hex keys are held down.
+    pub keys: [bool; 16],
+    key_wait: Option<u8>,
+    rng: Chip8Rng,
+    /// The interpreter variant being emulated.
+    pub quirks: Quirks,
+    drawn_this_frame: bool,
+    /// The display, 64x32 or 128x64 in SUPER-CHIP high resolution.
+    pub screen: Framebuffer,
+    /// The SUPER-CHIP "RPL" user flags saved by `SRPL` and restored by `LRPL`.
+    pub rpl: [u8; 16],
+    /// Set by `EXIT`, after which no more instructions are executed.
+    pub halted: bool,
+    /// The XO-CHIP bitplanes selected by `PLANE`, drawn and cleared by the display instructions.
+    pub plane: u8,
+    /// The 1-bit XO-CHIP sample loaded by `AUDIO` and played while the sound timer runs.
+    pub audio_pattern: [u8; 16],
+    /// The XO-CHIP playback rate set by `PITCH`, 64 plays the pattern at 4000 Hz.
+    pub pitch: u8,
+    accesses: Vec<MemAccess>,
+    /// Whether `Chip8Io::sound` was last told the sound is on.
+    sound_on: bool,
 }
 
 impl Chip8State {
@@ -35,176 +91,136 @@ impl Chip8State {
             pc: 0x0200,
             delay: 0,
             sound: 0,
-            timer_start: Local::now(),
+            clock: Box::new(RealClock::new()),
             timer_updates: 0,
-            mem: [0; 0x1000],
+            mem: vec![0; MEM_SIZE],
+            keys: [false; 16],
+            key_wait: None,
+            rng: Chip8Rng::from_entropy(),
+            quirks: Quirks::cosmac_vip(),
+            drawn_this_frame: false,
+            screen: Framebuffer::new(LORES_WIDTH, LORES_HEIGHT),
+            rpl: [0; 16],
+            halted: false,
+            plane: 1,
+            audio_pattern: [0; 16],
+            pitch: DEFAULT_PITCH,
+            accesses: vec![],
+            sound_on: false,
         };
-        state.load_font();
+        state.load_font(&font::DEFAULT);
+        state.load_big_font(&font::BIG);
         return state;
     }
 
-    pub fn load_program(&mut self, program: &Chip8Program) {
-        let mut addr = 0x0200;
-        for instruction in program.instructions.iter() {
-            self.mem[addr] = ((instruction & 0xff00) >> 8) as u8;
-            self.mem[addr + 1] = (instruction & 0x00ff) as u8;
-            addr += 2;
+    /// Replaces the clock that drives the delay and sound timers.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.timer_updates = clock.ticks();
+        self.clock = clock;
+    }
+
+    /// Restarts the generator behind `RAND` from `seed`, making runs reproducible.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Chip8Rng::new(seed);
+    }
+
+    pub fn rng_seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    /// Advances the clock by one 60 Hz tick and updates the timers.
+    pub fn tick_60hz(&mut self) {
+        self.clock.tick_60hz();
+        self.update_timers();
+    }
+
+    /// Copies `program` to memory at 0x200, failing if it runs past the end of memory.
+    pub fn load_program(&mut self, program: &Chip8Program) -> Result<(), String> {
+        let bytes = program.to_bytes();
+        if bytes.len() > MAX_PROGRAM_SIZE {
+            return Err(format!("Program of {} bytes doesn't fit in memory, the limit is {}", bytes.len(), MAX_PROGRAM_SIZE));
         }
+        let start = PROGRAM_START as usize;
+        self.mem[start..start + bytes.len()].copy_from_slice(&bytes);
+        Ok(())
     }
 
-    fn load_font(&mut self) {
-        // See http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#2.4 for reference
-
-        // 0
-        let start = FONT_START;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x90;
-        self.mem[(start + 2) as usize] = 0x90;
-        self.mem[(start + 3) as usize] = 0x90;
-        self.mem[(start + 4) as usize] = 0xf0;
-
-        // 1
-        let start = FONT_START + 1 * 5;
-        self.mem[(start + 0) as usize] = 0x20;
-        self.mem[(start + 1) as usize] = 0x60;
-        self.mem[(start + 2) as usize] = 0x20;
-        self.mem[(start + 3) as usize] = 0x20;
-        self.mem[(start + 4) as usize] = 0x70;
-
-        // 2
-        let start = FONT_START + 2 * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x10;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x80;
-        self.mem[(start + 4) as usize] = 0xf0;
-
-        // 3
-        let start = FONT_START + 3 * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x10;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x10;
-        self.mem[(start + 4) as usize] = 0xf0;
-
-        // 4
-        let start = FONT_START + 4 * 5;
-        self.mem[(start + 0) as usize] = 0x90;
-        self.mem[(start + 1) as usize] = 0x90;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x10;
-        self.mem[(start + 4) as usize] = 0x10;
-
-        // 5
-        let start = FONT_START + 5 * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x80;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x10;
-        self.mem[(start + 4) as usize] = 0xf0;
-
-        // 6
-        let start = FONT_START + 6 * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x80;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x90;
-        self.mem[(start + 4) as usize] = 0xf0;
-
-        // 7
-        let start = FONT_START + 7 * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x10;
-        self.mem[(start + 2) as usize] = 0x20;
-        self.mem[(start + 3) as usize] = 0x40;
-        self.mem[(start + 4) as usize] = 0x40;
-
-        // 8
-        let start = FONT_START + 8 * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x90;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x90;
-        self.mem[(start + 4) as usize] = 0xf0;
-
-        // 9
-        let start = FONT_START + 9 * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x90;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x10;
-        self.mem[(start + 4) as usize] = 0xf0;
-
-        // a
-        let start = FONT_START + 0xa * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x90;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x90;
-        self.mem[(start + 4) as usize] = 0x90;
-
-        // b
-        let start = FONT_START + 0xb * 5;
-        self.mem[(start + 0) as usize] = 0xe0;
-        self.mem[(start + 1) as usize] = 0x90;
-        self.mem[(start + 2) as usize] = 0xe0;
-        self.mem[(start + 3) as usize] = 0x90;
-        self.mem[(start + 4) as usize] = 0xe0;
-
-        // c
-        let start = FONT_START + 0xc * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x80;
-        self.mem[(start + 2) as usize] = 0x80;
-        self.mem[(start + 3) as usize] = 0x80;
-        self.mem[(start + 4) as usize] = 0xf0;
-
-        // d
-        let start = FONT_START + 0xd * 5;
-        self.mem[(start + 0) as usize] = 0xe0;
-        self.mem[(start + 1) as usize] = 0x90;
-        self.mem[(start + 2) as usize] = 0x90;
-        self.mem[(start + 3) as usize] = 0x90;
-        self.mem[(start + 4) as usize] = 0xe0;
-
-        // e
-        let start = FONT_START + 0xe * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x80;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x80;
-        self.mem[(start + 4) as usize] = 0xf0;
-
-        // f
-        let start = FONT_START + 0xf * 5;
-        self.mem[(start + 0) as usize] = 0xf0;
-        self.mem[(start + 1) as usize] = 0x80;
-        self.mem[(start + 2) as usize] = 0xf0;
-        self.mem[(start + 3) as usize] = 0x80;
-        self.mem[(start + 4) as usize] = 0x80;
+    pub fn load_font(&mut self, font: &[u8; FONT_SIZE]) {
+        let start = FONT_START as usize;
+        self.mem[start..start + FONT_SIZE].copy_from_slice(font);
+    }
+
+    /// Loads the 8x10 SUPER-CHIP font used by `XSPRITE`.
+    pub fn load_big_font(&mut self, font: &[u8; BIG_FONT_SIZE]) {
+        let start = BIG_FONT_START as usize;
+        self.mem[start..start + BIG_FONT_SIZE].copy_from_slice(font);
+    }
+
+    pub fn get_next_word(&self) -> Option<u16> {
+        self.word_at(self.pc)
     }
 
     pub fn get_next_opcode(&self) -> Option<Opcode> {
-        let instruction: u16 = ((self.mem[self.pc as usize] as u16) << 8) |
-            ((self.mem[self.pc as usize + 1]) as u16);
-        Opcode::new(instruction)
+        self.opcode_at(self.pc)
     }
 
-    pub fn exec_step(&mut self, get_char_fn: fn () -> u8) {
-        self.update_timers();
-        let opcode = self.get_next_opcode();
-        if let None = opcode {
-            panic!("Failed to decode instruction {:x}", self.pc);
+    /// Decodes the instruction at `addr`, including the second word of a `LONGI`.
+    pub fn opcode_at(&self, addr: u16) -> Option<Opcode> {
+        let next = self.word_at(addr.wrapping_add(2));
+        self.word_at(addr).and_then(|word| Opcode::decode(word, next))
+    }
+
+    fn word_at(&self, addr: u16) -> Option<u16> {
+        let addr = addr as usize;
+        if addr + 1 >= self.mem.len() {
+            return None;
         }
-        let opcode = opcode.unwrap();
+        Some(((self.mem[addr] as u16) << 8) | (self.mem[addr + 1] as u16))
+    }
+
+    /// Updates the pressed state of a key on the hex keypad. Called by the frontend.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.keys[(key & 0xf) as usize] = pressed;
+    }
+
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        self.keys[(key & 0xf) as usize]
+    }
+
+    /// Executes the instruction at `pc`, using `io` for keys and sound.
+    pub fn exec_step<T: Chip8Io>(&mut self, io: &mut T) -> Result<(), Chip8Fault> {
+        self.accesses.clear();
+        if self.halted {
+            return Ok(());
+        }
+        io.update_keys(&mut self.keys);
+        self.clock.instruction();
+        self.update_timers();
+
+        let pc = self.pc;
+        let word = match self.get_next_word() {
+            Some(word) => word,
+            None => return Err(Chip8Fault { pc, opcode: 0, kind: FaultKind::MemoryOutOfBounds(pc) }),
+        };
+        let opcode = match Opcode::decode(word, self.word_at(pc.wrapping_add(2))) {
+            Some(opcode) => opcode,
+            None => return Err(Chip8Fault { pc, opcode: word, kind: FaultKind::InvalidOpcode }),
+        };
+
+        self.exec_opcode(opcode, io).map_err(|kind| Chip8Fault { pc, opcode: word, kind })
+    }
+
+    fn exec_opcode<T: Chip8Io>(&mut self, opcode: Opcode, io: &mut T) -> Result<(), FaultKind> {
+        let size = opcode.size();
         let mut skip_inc_pc = false;
         match opcode {
-            Opcode::CLS => for i in DISP_START..0xFFF + 1 {
-                self.mem[i as usize] = 0x00;
+            Opcode::CLS => {
+                self.screen.clear(self.plane);
             }
             Opcode::RET => {
-                let pc = self.stack_pop();
-                self.pc = pc;
+                // The stack holds the address of the CALL, return to the instruction after it
+                let pc = self.stack_pop()?;
+                self.pc = pc.wrapping_add(2);
                 skip_inc_pc = true;
             }
             Opcode::JMP(n) => {
@@ -213,25 +229,25 @@ impl Chip8State {
             }
             Opcode::CALL(n) => {
                 let pc = self.pc;
-                self.stack_push(pc);
+                self.stack_push(pc)?;
                 self.pc = n;
                 skip_inc_pc = true;
             }
             Opcode::SKIPEQ(x, n) => {
                 if self.vreg_val(&x) == n {
-                    self.pc += 4;
+                    self.skip_next();
                     skip_inc_pc = true;
                 }
             }
             Opcode::SKIPNEQ(x, n) => {
                 if self.vreg_val(&x) != n {
-                    self.pc += 4;
+                    self.skip_next();
                     skip_inc_pc = true;
                 }
             }
             Opcode::SKIPREQ(x, y) => {
                 if self.vreg_val(&x) == self.vreg_val(&y) {
-                    self.pc += 4;
+                    self.skip_next();
                     skip_inc_pc = true;
                 }
             }
@@ -250,18 +266,27 @@ impl Chip8State {
                 let y_val = self.vreg_val(&y);
                 let new_val = x_val | y_val;
                 self.set_vreg_val(&x, new_val);
+                if self.quirks.vf_reset {
+                    self.vregs[0xf] = 0;
+                }
             }
             Opcode::AND(x, y) => {
                 let x_val = self.vreg_val(&x);
                 let y_val = self.vreg_val(&y);
                 let new_val = x_val & y_val;
                 self.set_vreg_val(&x, new_val);
+                if self.quirks.vf_reset {
+                    self.vregs[0xf] = 0;
+                }
             }
             Opcode::XOR(x, y) => {
                 let x_val = self.vreg_val(&x);
                 let y_val = self.vreg_val(&y);
                 let new_val = x_val ^ y_val;
                 self.set_vreg_val(&x, new_val);
+                if self.quirks.vf_reset {
+                    self.vregs[0xf] = 0;
+                }
             }
             Opcode::ADDR(x, y) => {
                 let x_val = self.vreg_val(&x);
@@ -278,11 +303,10 @@ impl Chip8State {
                 self.vregs[0xf] = carry as u8;
             }
             Opcode::SR(x, y) => {
-                let y_val = self.vreg_val(&y);
-                let lsb = y_val & 0x1;
-                let new_val = y_val >> 1;
+                let src = self.vreg_val(if self.quirks.shift_vy { &y } else { &x });
+                let lsb = src & 0x1;
+                let new_val = src >> 1;
                 self.set_vreg_val(&x, new_val);
-                self.set_vreg_val(&y, new_val);
                 self.vregs[0xf] = lsb;
             }
             Opcode::RSUBR(x, y) => {
@@ -293,50 +317,85 @@ impl Chip8State {
                 self.vregs[0xf] = carry as u8;
             }
             Opcode::SL(x, y) => {
-                let y_val = self.vreg_val(&y);
-                let msb = (y_val & 0b10000000) >> 7;
-                let new_val = y_val << 1;
+                let src = self.vreg_val(if self.quirks.shift_vy { &y } else { &x });
+                let msb = (src & 0b10000000) >> 7;
+                let new_val = src << 1;
                 self.set_vreg_val(&x, new_val);
-                self.set_vreg_val(&y, new_val);
                 self.vregs[0xf] = msb;
             }
             Opcode::SKIPRNEQ(x, y) => {
                 if self.vreg_val(&x) != self.vreg_val(&y) {
-                    self.pc += 4;
+                    self.skip_next();
                     skip_inc_pc = true;
                 }
             }
             Opcode::SI(n) => self.i = n,
             Opcode::JMPR(n) => {
-                self.pc = n + (self.vregs[0x0] as u16);
+                let reg = if self.quirks.jump_vx { (n >> 8) as usize } else { 0x0 };
+                self.pc = n + (self.vregs[reg] as u16);
                 skip_inc_pc = true;
             }
             Opcode::RAND(x, n) => {
-                self.set_vreg_val(&x, random(n));
+                let val = self.rng.next_u8() & n;
+                self.set_vreg_val(&x, val);
+            }
+            Opcode::DRAW(_, _, _) if self.quirks.display_wait && self.drawn_this_frame => {
+                skip_inc_pc = true;
             }
             Opcode::DRAW(x, y, n) => {
-                for height in 0..n {
-                    let mut row: u8 = self.mem[(self.i + height as u16) as usize];
-                    for width in 0..8 {
-                        let val: bool = row & (1 << 7) != 0;
-                        row = row << 1;
-                        let (x, _) = self.vreg_val(&x).overflowing_add(width);
-                        let (y, _) = self.vreg_val(&y).overflowing_add(height);
-                        if x < 64 && y < 32 {
-                            self.write_pixel(x, y, val);
+                // The origin always wraps around the screen, the sprite itself may be clipped at the edges
+                let clip = self.quirks.clip_sprites;
+                let (w, h) = (self.screen.width(), self.screen.height());
+                let x0 = self.vreg_val(&x) as usize % w;
+                let y0 = self.vreg_val(&y) as usize % h;
+                // DXY0 draws a 16x16 sprite made of two bytes per row
+                let (cols, rows) = if n == 0 { (16, 16) } else { (8, n as usize) };
+                let bytes_per_row = cols / 8;
+                let sprite_len = rows * bytes_per_row;
+                // Each selected plane takes its own copy of the sprite data, one after another
+                let planes: Vec<u8> = [1, 2].iter().cloned().filter(|p| self.plane & p != 0).collect();
+                let mut start = self.mem_range(self.i, sprite_len * planes.len())?;
+                let mut collision = false;
+                for plane in planes {
+                    for row in 0..rows {
+                        let mut y = y0 + row;
+                        if y >= h {
+                            if clip {
+                                break;
+                            }
+                            y %= h;
+                        }
+                        let mut bits: u16 = 0;
+                        for b in 0..bytes_per_row {
+                            bits = (bits << 8) | self.read_mem(start + row * bytes_per_row + b) as u16;
+                        }
+                        for col in 0..cols {
+                            let mut x = x0 + col;
+                            if x >= w {
+                                if clip {
+                                    break;
+                                }
+                                x %= w;
+                            }
+                            if bits & (1 << (cols - 1 - col)) != 0 {
+                                collision |= self.screen.xor_pixel(x, y, plane);
+                            }
                         }
                     }
+                    start += sprite_len;
                 }
+                self.vregs[0xf] = collision as u8;
+                self.drawn_this_frame = true;
             }
             Opcode::SKIPKEQ(x) => {
-                if self.vreg_val(&x) == get_char_fn() {
-                    self.pc += 4;
+                if self.is_key_pressed(self.vreg_val(&x)) {
+                    self.skip_next();
                     skip_inc_pc = true;
                 }
             }
             Opcode::SKIPKNEQ(x) => {
-                if self.vreg_val(&x) != get_char_fn() {
-                    self.pc += 4;
+                if !self.is_key_pressed(self.vreg_val(&x)) {
+                    self.skip_next();
                     skip_inc_pc = true;
                 }
             }
@@ -345,7 +404,20 @@ impl Chip8State {
                 self.set_vreg_val(&x, delay);
             }
             Opcode::GKEY(x) => {
-                self.set_vreg_val(&x, get_char_fn());
+                // Like the COSMAC VIP, wait until a key is pressed and released again
+                skip_inc_pc = true;
+                match self.key_wait {
+                    Some(key) if !self.is_key_pressed(key) => {
+                        self.set_vreg_val(&x, key);
+                        self.key_wait = None;
+                        skip_inc_pc = false;
+                    }
+                    Some(_) => {}
+                    None => self.key_wait = self.keys.iter().position(|&k| k).map(|k| k as u8),
+                }
+                if skip_inc_pc {
+                    io.wait_key();
+                }
             }
             Opcode::SDELAY(x) => {
                 let delay = self.vreg_val(&x);
@@ -356,49 +428,126 @@ impl Chip8State {
                 self.sound = snd;
             }
             Opcode::ADDI(x) => {
-                let sum = self.i + self.vreg_val(&x) as u16;
+                let sum = self.i.wrapping_add(self.vreg_val(&x) as u16);
                 self.i = sum;
             }
             Opcode::SPRITE(x) => {
                 let c = self.vreg_val(&x);
-                assert!(c <= 0xf);
+                if c > 0xf {
+                    return Err(FaultKind::InvalidFontChar(c));
+                }
                 self.i = (c * 5) as u16 + FONT_START;
             }
+            Opcode::XSPRITE(x) => {
+                let c = self.vreg_val(&x);
+                if c > 0xf {
+                    return Err(FaultKind::InvalidFontChar(c));
+                }
+                self.i = (c as u16 * 10) + BIG_FONT_START;
+            }
             Opcode::BCD(x) => {
                 let val = self.vreg_val(&x);
                 let hundreds: u8 = val / 100;
                 let tens: u8 = (val % 100) / 10;
                 let ones: u8 = val % 10;
-                self.mem[self.i as usize] = hundreds;
-                self.mem[(self.i + 1) as usize] = tens;
-                self.mem[(self.i + 2) as usize] = ones;
+                let start = self.mem_range(self.i, 3)?;
+                self.write_mem(start, hundreds);
+                self.write_mem(start + 1, tens);
+                self.write_mem(start + 2, ones);
             }
             Opcode::RDUMP(x) => {
-                for reg in 0..x.v + 1 {
-                    self.mem[(self.i + reg as u16) as usize] = self.vregs[reg as usize];
+                let start = self.mem_range(self.i, x.v as usize + 1)?;
+                for reg in 0..x.v as usize + 1 {
+                    let val = self.vregs[reg];
+                    self.write_mem(start + reg, val);
                 }
-                self.i += x.v as u16 + 1;
+                self.i = self.i.wrapping_add(self.load_store_increment(x));
             }
             Opcode::RLOAD(x) => {
-                for reg in 0..x.v + 1 {
-                    self.vregs[reg as usize] = self.mem[(self.i + reg as u16) as usize];
+                let start = self.mem_range(self.i, x.v as usize + 1)?;
+                for reg in 0..x.v as usize + 1 {
+                    self.vregs[reg] = self.read_mem(start + reg);
+                }
+                self.i = self.i.wrapping_add(self.load_store_increment(x));
+            }
+            Opcode::SCD(n) => {
+                self.screen.scroll_down(n as usize, self.plane);
+            }
+            Opcode::SCR => {
+                self.screen.scroll_right(4, self.plane);
+            }
+            Opcode::SCL => {
+                self.screen.scroll_left(4, self.plane);
+            }
+            Opcode::EXIT => {
+                self.halted = true;
+                skip_inc_pc = true;
+            }
+            Opcode::LOW => {
+                self.screen.resize(LORES_WIDTH, LORES_HEIGHT);
+            }
+            Opcode::HIGH => {
+                self.screen.resize(HIRES_WIDTH, HIRES_HEIGHT);
+            }
+            Opcode::SRPL(x) => {
+                for reg in 0..x.v as usize + 1 {
+                    self.rpl[reg] = self.vregs[reg];
+                }
+            }
+            Opcode::LRPL(x) => {
+                for reg in 0..x.v as usize + 1 {
+                    self.vregs[reg] = self.rpl[reg];
+                }
+            }
+            Opcode::LONGI(n) => self.i = n,
+            Opcode::SAVER(x, y) => {
+                let regs = Chip8State::reg_range(x, y);
+                let start = self.mem_range(self.i, regs.len())?;
+                for (offset, reg) in regs.into_iter().enumerate() {
+                    let val = self.vregs[reg];
+                    self.write_mem(start + offset, val);
+                }
+            }
+            Opcode::LOADR(x, y) => {
+                let regs = Chip8State::reg_range(x, y);
+                let start = self.mem_range(self.i, regs.len())?;
+                for (offset, reg) in regs.into_iter().enumerate() {
+                    self.vregs[reg] = self.read_mem(start + offset);
+                }
+            }
+            Opcode::PLANE(n) => self.plane = n & ALL_PLANES,
+            Opcode::AUDIO => {
+                let start = self.mem_range(self.i, 16)?;
+                for n in 0..16 {
+                    self.audio_pattern[n] = self.read_mem(start + n);
                 }
-                self.i += x.v as u16 + 1;
             }
+            Opcode::PITCH(x) => self.pitch = self.vreg_val(&x),
         }
         if !skip_inc_pc {
-            self.pc += 2;
+            self.pc = self.pc.wrapping_add(size);
+        }
+        self.sync_sound(io);
+        if self.screen.take_dirty() {
+            io.frame_ready(self);
+        }
+        Ok(())
+    }
+
+    /// Tells `io` when the sound timer started or ran out since the last call.
+    pub fn sync_sound<T: Chip8Io>(&mut self, io: &mut T) {
+        let on = self.sound > 0;
+        if on != self.sound_on {
+            self.sound_on = on;
+            io.sound(on);
         }
     }
 
     pub fn update_timers(&mut self) {
-        let now = Local::now();
-        let duration = now.signed_duration_since(self.timer_start);
-        let ms = duration.num_milliseconds() as u64;
-        let expected_updates = (ms * 60) / 1000;
-        let updates_to_makeup = expected_updates - self.timer_updates;
-        for _ in 0..updates_to_makeup {
+        let expected_updates = self.clock.ticks();
+        while self.timer_updates < expected_updates {
             self.timer_updates += 1;
+            self.drawn_this_frame = false;
 
             if self.sound > 0 {
                 self.sound -= 1;
@@ -409,6 +558,22 @@ impl Chip8State {
         }
     }
 
+    /// Skips the next instruction, which is 4 bytes long for `F000 NNNN`.
+    fn skip_next(&mut self) {
+        let next = self.pc.wrapping_add(2);
+        let len = if self.word_at(next) == Some(0xf000) { 4 } else { 2 };
+        self.pc = next.wrapping_add(len);
+    }
+
+    /// The registers from VX to VY in order, counting down if X is greater than Y.
+    fn reg_range(x: VReg, y: VReg) -> Vec<usize> {
+        let (x, y) = (x.v as usize, y.v as usize);
+        if x <= y {
+            (x..y + 1).collect()
+        } else {
+            (y..x + 1).rev().collect()
+        }
+    }
 
     fn vreg_val(&self, vreg: &VReg) -> u8 {
         self.vregs[vreg.v as usize]
@@ -418,54 +583,218 @@ impl Chip8State {
         self.vregs[vreg.v as usize] = val;
     }
 
-    fn stack_push(&mut self, val: u16) {
-        assert_ne!(self.sp, 16);
+    /// Checks that `len` bytes starting at `addr` are in memory, returning the start index.
+    fn mem_range(&self, addr: u16, len: usize) -> Result<usize, FaultKind> {
+        let end = addr as usize + len;
+        if end > self.mem.len() {
+            return Err(FaultKind::MemoryOutOfBounds(addr));
+        }
+        Ok(addr as usize)
+    }
+
+    /// How far `RDUMP`/`RLOAD` of V0 to `x` move I.
+    fn load_store_increment(&self, x: VReg) -> u16 {
+        match self.quirks.load_store_i {
+            LoadStoreI::PastLast => x.v as u16 + 1,
+            LoadStoreI::AtLast => x.v as u16,
+            LoadStoreI::Unchanged => 0,
+        }
+    }
+
+    /// Reads a byte of data, logging the access. `addr` must already be checked with `mem_range`.
+    fn read_mem(&mut self, addr: usize) -> u8 {
+        let value = self.mem[addr];
+        self.accesses.push(MemAccess { addr: addr as u16, value, write: false, stack: false });
+        value
+    }
+
+    fn write_mem(&mut self, addr: usize, value: u8) {
+        self.mem[addr] = value;
+        self.accesses.push(MemAccess { addr: addr as u16, value, write: true, stack: false });
+    }
+
+    fn read_stack(&mut self, addr: usize) -> u8 {
+        let value = self.mem[addr];
+        self.accesses.push(MemAccess { addr: addr as u16, value, write: false, stack: true });
+        value
+    }
+
+    fn write_stack(&mut self, addr: usize, value: u8) {
+        self.mem[addr] = value;
+        self.accesses.push(MemAccess { addr: addr as u16, value, write: true, stack: true });
+    }
+
+    /// The data reads and writes made by the last `exec_step`, in order. Instruction fetches
+    /// aren't included.
+    pub fn last_accesses(&self) -> &[MemAccess] {
+        &self.accesses
+    }
+
+    fn stack_push(&mut self, val: u16) -> Result<(), FaultKind> {
+        if self.sp >= 16 {
+            return Err(FaultKind::StackOverflow);
+        }
         let upper: u8 = (val >> 8) as u8;
         let lower: u8 = (val & 0xff) as u8;
-        self.mem[(STACK_START + (self.sp * 2) as u16) as usize] = upper;
-        self.mem[(STACK_START + (self.sp * 2 + 1) as u16) as usize] = lower;
+        let addr = (STACK_START + (self.sp * 2) as u16) as usize;
+        self.write_stack(addr, upper);
+        self.write_stack(addr + 1, lower);
         self.sp += 1;
+        Ok(())
     }
 
-    fn stack_pop(&mut self) -> u16 {
-        assert_ne!(self.sp, 0);
+    fn stack_pop(&mut self) -> Result<u16, FaultKind> {
+        if self.sp == 0 || self.sp > 16 {
+            return Err(FaultKind::StackUnderflow);
+        }
         self.sp -= 1;
-        let upper = self.mem[(STACK_START + (self.sp * 2) as u16) as usize];
-        let lower = self.mem[(STACK_START + (self.sp * 2 + 1) as u16) as usize];
+        let addr = (STACK_START + (self.sp * 2) as u16) as usize;
+        let upper = self.read_stack(addr);
+        let lower = self.read_stack(addr + 1);
 
-        ((upper as u16) << 8) | (lower as u16)
+        Ok(((upper as u16) << 8) | (lower as u16))
     }
 
-    fn decode_pixel(x: u8, y: u8) -> (u16, u8) {
-        assert!(x < 64);
-        assert!(y < 32);
-        let addr = DISP_START as usize + (x as usize / 8) + y as usize * 8;
-        assert!(addr <= 0xFFF);
-        let mask = 0x1 << (x % 8);
-        (addr as u16, mask)
+    /// The addresses of the active `CALL` instructions, outermost first.
+    pub fn call_stack(&self) -> Vec<u16> {
+        (0..self.sp.min(16) as u16)
+            .map(|n| {
+                let addr = (STACK_START + n * 2) as usize;
+                ((self.mem[addr] as u16) << 8) | self.mem[addr + 1] as u16
+            })
+            .collect()
     }
 
-    pub fn pixel_on(&self, x: u8, y: u8) -> bool {
-        let (addr, mask) = Chip8State::decode_pixel(x, y);
-        self.mem[addr as usize] & mask != 0
+    pub fn pixel_on(&self, x: usize, y: usize) -> bool {
+        self.screen.pixel(x, y)
     }
 
-    fn write_pixel(&mut self, x: u8, y: u8, val: bool) -> bool {
-        let (addr, mask) = Chip8State::decode_pixel(x, y);
-        let current = self.mem[addr as usize] & mask != 0;
+    /// Sets or clears a pixel in the selected planes.
+    pub fn write_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let planes = self.plane;
+        self.screen.write_pixel(x, y, planes, on);
+    }
 
-        if current != val {
-            self.mem[addr as usize] = (self.mem[addr as usize] & !mask) | (mask & if val { 0xff } else { 0 });
+    /// Serializes the complete machine state, see `savestate` for the format.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = SaveWriter::new();
+        w.bytes(&self.vregs);
+        w.u16(self.i);
+        w.u8(self.sp);
+        w.u16(self.pc);
+        w.u8(self.delay);
+        w.u8(self.sound);
+        w.u64(self.timer_updates);
+        w.bytes(&self.mem);
+        for &key in self.keys.iter() {
+            w.bool(key);
+        }
+        w.bool(self.key_wait.is_some());
+        w.u8(self.key_wait.unwrap_or(0));
+        w.u64(self.rng.seed());
+        w.u64(self.rng.state());
+        let q = &self.quirks;
+        w.bool(q.shift_vy);
+        // Stored where a flag used to be, so older states still load
+        w.u8(match q.load_store_i {
+            LoadStoreI::Unchanged => 0,
+            LoadStoreI::PastLast => 1,
+            LoadStoreI::AtLast => 2,
+        });
+        let quirks = [q.jump_vx, q.vf_reset, q.display_wait, q.clip_sprites];
+        for &quirk in quirks.iter() {
+            w.bool(quirk);
         }
+        w.bool(self.drawn_this_frame);
+        w.u16(self.screen.width() as u16);
+        w.u16(self.screen.height() as u16);
+        let mut pixels = Vec::with_capacity(self.screen.width() * self.screen.height());
+        for y in 0..self.screen.height() {
+            for x in 0..self.screen.width() {
+                pixels.push(self.screen.planes(x, y));
+            }
+        }
+        w.bytes(&pixels);
+        w.bytes(&self.rpl);
+        w.bool(self.halted);
+        w.u8(self.plane);
+        w.bytes(&self.audio_pattern);
+        w.u8(self.pitch);
+        w.finish()
+    }
 
-        !(current && !val)
+    /// Restores a state written by `save_state`. Nothing is changed if it is invalid.
+    ///
+    /// Timers resume on a `VirtualClock` from the saved tick count.
+    pub fn load_state(&mut self, buf: &[u8]) -> Result<(), SaveStateError> {
+        let mut r = SaveReader::new(buf)?;
+        let mut state = Chip8State::new();
+
+        copy_exact(&mut state.vregs, r.bytes()?, "register file")?;
+        state.i = r.u16()?;
+        state.sp = r.u8()?;
+        if state.sp > 16 {
+            return Err(SaveStateError::Invalid("stack pointer"));
+        }
+        state.pc = r.u16()?;
+        state.delay = r.u8()?;
+        state.sound = r.u8()?;
+        state.timer_updates = r.u64()?;
+        copy_exact(&mut state.mem, r.bytes()?, "memory size")?;
+        for key in state.keys.iter_mut() {
+            *key = r.bool()?;
+        }
+        let waiting = r.bool()?;
+        let key = r.u8()?;
+        state.key_wait = if waiting { Some(key & 0xf) } else { None };
+        let seed = r.u64()?;
+        state.rng = Chip8Rng::from_parts(seed, r.u64()?);
+        state.quirks = Quirks {
+            shift_vy: r.bool()?,
+            load_store_i: match r.u8()? {
+                0 => LoadStoreI::Unchanged,
+                1 => LoadStoreI::PastLast,
+                2 => LoadStoreI::AtLast,
+                _ => return Err(SaveStateError::Invalid("quirk")),
+            },
+            jump_vx: r.bool()?,
+            vf_reset: r.bool()?,
+            display_wait: r.bool()?,
+            clip_sprites: r.bool()?,
+        };
+        state.drawn_this_frame = r.bool()?;
+        let (width, height) = (r.u16()? as usize, r.u16()? as usize);
+        let size = (width, height);
+        if size != (LORES_WIDTH, LORES_HEIGHT) && size != (HIRES_WIDTH, HIRES_HEIGHT) {
+            return Err(SaveStateError::Invalid("screen size"));
+        }
+        state.screen.resize(width, height);
+        let pixels = r.bytes()?;
+        if pixels.len() != width * height || pixels.iter().any(|&p| p & !ALL_PLANES != 0) {
+            return Err(SaveStateError::Invalid("screen"));
+        }
+        for (n, &planes) in pixels.iter().enumerate() {
+            state.screen.write_pixel(n % width, n / width, planes, true);
+        }
+        copy_exact(&mut state.rpl, r.bytes()?, "flag registers")?;
+        state.halted = r.bool()?;
+        state.plane = r.u8()? & ALL_PLANES;
+        copy_exact(&mut state.audio_pattern, r.bytes()?, "audio pattern")?;
+        state.pitch = r.u8()?;
+        r.finish()?;
+
+        state.clock = Box::new(VirtualClock::starting_at(state.timer_updates));
+        *self = state;
+        Ok(())
     }
 }
 
-fn random(mask: u8) -> u8 {
-    let mut rng = rand::thread_rng();
-    let rand = rng.gen::<u8>() & mask;
-    rand
+fn copy_exact(dst: &mut [u8], src: &[u8], what: &'static str) -> Result<(), SaveStateError> {
+    if dst.len() != src.len() {
+        return Err(SaveStateError::Invalid(what));
+    }
+    dst.copy_from_slice(src);
+    Ok(())
 }
 
 impl fmt::Debug for Chip8State {
@@ -487,6 +816,9 @@ impl cmp::PartialEq for Chip8State {
     fn eq(&self, other: &Chip8State) -> bool {
         self.vregs == other.vregs && self.i == other.i && self.sp == other.sp &&
             self.pc == other.pc && self.delay == other.delay && self.sound == other.sound &&
+            self.keys == other.keys && self.screen == other.screen && self.rpl == other.rpl &&
+            self.halted == other.halted && self.plane == other.plane &&
+            self.audio_pattern == other.audio_pattern && self.pitch == other.pitch &&
             {
                 let mut eq = true;
                 for i in 0..self.mem.len() {
@@ -504,14 +836,34 @@ impl cmp::PartialEq for Chip8State {
 #[cfg(test)]
 mod tests {
 
-    fn fake_getchar() -> u8 {0}
-
     use super::*;
+    use io::NullIo;
+    use clock::VirtualClock;
+
+    struct TestIo {
+        keys: [bool; 16],
+        sound: Vec<bool>,
+        frames: usize,
+    }
+
+    impl Chip8Io for TestIo {
+        fn update_keys(&mut self, keys: &mut [bool; 16]) {
+            *keys = self.keys;
+        }
+
+        fn sound(&mut self, on: bool) {
+            self.sound.push(on);
+        }
+
+        fn frame_ready(&mut self, _state: &Chip8State) {
+            self.frames += 1;
+        }
+    }
 
     #[test]
     fn test_load_program() {
         let mut tmp = Chip8State::new();
-        tmp.load_program(&Chip8Program::new(&[0u8; 0]));
+        tmp.load_program(&Chip8Program::new(&[0u8; 0])).unwrap();
         assert_eq!(Chip8State::new(), tmp);
 
         let mut tmp = Chip8State::new();
@@ -519,7 +871,7 @@ mod tests {
         assert_eq!(0x00, tmp.mem[0x0200]);
         assert_eq!(0x00, tmp.mem[0x0201]);
         assert_eq!(0x00, tmp.mem[0x0202]);
-        tmp.load_program(&Chip8Program::new(&[0xab, 0xcd]));
+        tmp.load_program(&Chip8Program::new(&[0xab, 0xcd])).unwrap();
         assert_eq!(0x00, tmp.mem[0x01ff]);
         assert_eq!(0xab, tmp.mem[0x0200]);
         assert_eq!(0xcd, tmp.mem[0x0201]);
@@ -533,7 +885,7 @@ mod tests {
         assert_eq!(0x00, tmp.mem[0x0203]);
         assert_eq!(0x00, tmp.mem[0x0204]);
         assert_eq!(0x00, tmp.mem[0x0205]);
-        tmp.load_program(&Chip8Program::new(&[0xab, 0xcd, 0x12, 0x34]));
+        tmp.load_program(&Chip8Program::new(&[0xab, 0xcd, 0x12, 0x34])).unwrap();
         assert_eq!(0x00, tmp.mem[0x01ff]);
         assert_eq!(0xab, tmp.mem[0x0200]);
         assert_eq!(0xcd, tmp.mem[0x0201]);
@@ -550,7 +902,7 @@ mod tests {
         assert_eq!(0x00, tmp.mem[0x0203]);
         assert_eq!(0x00, tmp.mem[0x0204]);
         assert_eq!(0x00, tmp.mem[0x0205]);
-        tmp.load_program(&Chip8Program::new(&[0xab, 0xcd, 0x12, 0x34, 0x56]));
+        tmp.load_program(&Chip8Program::new(&[0xab, 0xcd, 0x12, 0x34, 0x56])).unwrap();
         assert_eq!(0x00, tmp.mem[0x01ff]);
         assert_eq!(0xab, tmp.mem[0x0200]);
         assert_eq!(0xcd, tmp.mem[0x0201]);
@@ -558,6 +910,11 @@ mod tests {
         assert_eq!(0x34, tmp.mem[0x0203]);
         assert_eq!(0x00, tmp.mem[0x0204]);
         assert_eq!(0x00, tmp.mem[0x0205]);
+
+        let mut tmp = Chip8State::new();
+        assert!(tmp.load_program(&Chip8Program::new(&vec![0xff; MAX_PROGRAM_SIZE])).is_ok());
+        assert_eq!(0xff, tmp.mem[0xffff]);
+        assert!(tmp.load_program(&Chip8Program::new(&vec![0xff; MAX_PROGRAM_SIZE + 2])).is_err());
     }
 
     #[test]
@@ -565,17 +922,17 @@ mod tests {
         assert_eq!(Chip8State::new(), Chip8State::new());
 
         let mut tmp = Chip8State::new();
-        tmp.load_program(&Chip8Program::new(&[0u8; 0]));
+        tmp.load_program(&Chip8Program::new(&[0u8; 0])).unwrap();
         assert_eq!(Chip8State::new(), tmp);
 
         let mut tmp = Chip8State::new();
-        tmp.load_program(&Chip8Program::new(&[0x00, 0x01]));
+        tmp.load_program(&Chip8Program::new(&[0x00, 0x01])).unwrap();
         assert_ne!(Chip8State::new(), tmp);
 
         let mut tmp1 = Chip8State::new();
         let mut tmp2 = Chip8State::new();
-        tmp1.load_program(&Chip8Program::new(&[0x00, 0x01]));
-        tmp2.load_program(&Chip8Program::new(&[0x00, 0x01]));
+        tmp1.load_program(&Chip8Program::new(&[0x00, 0x01])).unwrap();
+        tmp2.load_program(&Chip8Program::new(&[0x00, 0x01])).unwrap();
         assert_eq!(tmp1, tmp2);
 
         let mut tmp = Chip8State::new();
@@ -611,8 +968,8 @@ mod tests {
     fn test_exec_JMP() {
         let mut tmp = Chip8State::new();
         assert_eq!(0x0200, tmp.pc);
-        tmp.load_program(&Chip8Program::new(&[0x1a, 0xbc]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x1a, 0xbc])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0abc, tmp.pc);
     }
 
@@ -620,14 +977,14 @@ mod tests {
     fn test_exec_SKIPEQ() {
         let mut tmp = Chip8State::new();
         assert_eq!(0x0200, tmp.pc);
-        tmp.load_program(&Chip8Program::new(&[0x30, 0x00]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x30, 0x00])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0204, tmp.pc);
 
         let mut tmp = Chip8State::new();
         assert_eq!(0x0200, tmp.pc);
-        tmp.load_program(&Chip8Program::new(&[0x30, 0x01]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x30, 0x01])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0202, tmp.pc);
     }
 
@@ -635,14 +992,14 @@ mod tests {
     fn test_exec_SKIPNEQ() {
         let mut tmp = Chip8State::new();
         assert_eq!(0x0200, tmp.pc);
-        tmp.load_program(&Chip8Program::new(&[0x40, 0x00]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x40, 0x00])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0202, tmp.pc);
 
         let mut tmp = Chip8State::new();
         assert_eq!(0x0200, tmp.pc);
-        tmp.load_program(&Chip8Program::new(&[0x40, 0x01]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x40, 0x01])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0204, tmp.pc);
     }
 
@@ -650,15 +1007,15 @@ mod tests {
     fn test_exec_SKIPREQ() {
         let mut tmp = Chip8State::new();
         assert_eq!(0x0200, tmp.pc);
-        tmp.load_program(&Chip8Program::new(&[0x52, 0x20]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x52, 0x20])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0204, tmp.pc);
 
         let mut tmp = Chip8State::new();
         assert_eq!(0x0200, tmp.pc);
         tmp.vregs[4] = 0x32;
-        tmp.load_program(&Chip8Program::new(&[0x52, 0x40]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x52, 0x40])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0202, tmp.pc);
     }
 
@@ -666,15 +1023,15 @@ mod tests {
     fn test_exec_SKIPRNEQ() {
         let mut tmp = Chip8State::new();
         assert_eq!(0x0200, tmp.pc);
-        tmp.load_program(&Chip8Program::new(&[0x92, 0x20]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x92, 0x20])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0202, tmp.pc);
 
         let mut tmp = Chip8State::new();
         assert_eq!(0x0200, tmp.pc);
         tmp.vregs[4] = 0x32;
-        tmp.load_program(&Chip8Program::new(&[0x92, 0x40]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x92, 0x40])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0204, tmp.pc);
     }
 
@@ -682,8 +1039,8 @@ mod tests {
     fn test_exec_SI() {
         let mut tmp = Chip8State::new();
         assert_eq!(0x0000, tmp.i);
-        tmp.load_program(&Chip8Program::new(&[0xaa, 0xbc]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xaa, 0xbc])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0abc, tmp.i);
     }
 
@@ -691,8 +1048,8 @@ mod tests {
     fn test_exec_MOV() {
         let mut tmp = Chip8State::new();
         assert_eq!(0x00, tmp.vregs[5]);
-        tmp.load_program(&Chip8Program::new(&[0x65, 0xab]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x65, 0xab])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xab, tmp.vregs[5]);
     }
 
@@ -701,16 +1058,16 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0xcd;
         assert_eq!(0x00, tmp.vregs[0x7]);
-        tmp.load_program(&Chip8Program::new(&[0x87, 0xa0]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x87, 0xa0])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xcd, tmp.vregs[0x7]);
         assert_eq!(0xcd, tmp.vregs[0xa]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0xcd;
         assert_eq!(0x00, tmp.vregs[0x7]);
-        tmp.load_program(&Chip8Program::new(&[0x8a, 0x70]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x8a, 0x70])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x00, tmp.vregs[0x7]);
         assert_eq!(0x00, tmp.vregs[0xa]);
     }
@@ -720,16 +1077,16 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x5;
         tmp.vregs[0x2] = 0x2;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x21]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x21])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x5 | 0x2, tmp.vregs[0x1]);
         assert_eq!(0x2, tmp.vregs[0x2]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0b01010101;
         tmp.vregs[0x2] = 0b10101010;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x21]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x21])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0b11111111, tmp.vregs[0x1]);
         assert_eq!(0b10101010, tmp.vregs[0x2]);
     }
@@ -739,16 +1096,16 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x5;
         tmp.vregs[0x2] = 0x2;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x22]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x22])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x5 & 0x2, tmp.vregs[0x1]);
         assert_eq!(0x2, tmp.vregs[0x2]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0b01010101;
         tmp.vregs[0x2] = 0b10101011;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x22]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x22])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0b00000001, tmp.vregs[0x1]);
         assert_eq!(0b10101011, tmp.vregs[0x2]);
     }
@@ -758,16 +1115,16 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x5;
         tmp.vregs[0x2] = 0x2;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x23]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x23])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x5 ^ 0x2, tmp.vregs[0x1]);
         assert_eq!(0x2, tmp.vregs[0x2]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0b01010101;
         tmp.vregs[0x2] = 0b10101011;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x23]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x23])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0b11111110, tmp.vregs[0x1]);
         assert_eq!(0b10101011, tmp.vregs[0x2]);
     }
@@ -776,14 +1133,14 @@ mod tests {
     fn test_exec_ADD() {
         let mut tmp = Chip8State::new();
         assert_eq!(0x00, tmp.vregs[0xa]);
-        tmp.load_program(&Chip8Program::new(&[0x7a, 0xbc]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x7a, 0xbc])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xbc, tmp.vregs[0xa]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0x32;
-        tmp.load_program(&Chip8Program::new(&[0x7a, 0xab]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x7a, 0xab])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xab + 0x32, tmp.vregs[0xa]);
     }
 
@@ -792,8 +1149,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x5;
         tmp.vregs[0x2] = 0x2;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x24]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x24])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x7, tmp.vregs[0x1]);
         assert_eq!(0x2, tmp.vregs[0x2]);
         assert_eq!(0x0, tmp.vregs[0xf]);
@@ -801,8 +1158,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x34;
         tmp.vregs[0x2] = 0x24;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x24]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x24])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x34 + 0x24, tmp.vregs[0x1]);
         assert_eq!(0x24, tmp.vregs[0x2]);
         assert_eq!(0x0, tmp.vregs[0xf]);
@@ -810,8 +1167,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0xff;
         tmp.vregs[0x2] = 0x01;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x24]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x24])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0, tmp.vregs[0x1]);
         assert_eq!(0x1, tmp.vregs[0x2]);
         assert_eq!(0x1, tmp.vregs[0xf]);
@@ -819,8 +1176,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0xff;
         tmp.vregs[0x2] = 0x05;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x24]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x24])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x4, tmp.vregs[0x1]);
         assert_eq!(0x5, tmp.vregs[0x2]);
         assert_eq!(0x1, tmp.vregs[0xf]);
@@ -831,8 +1188,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x5;
         tmp.vregs[0x2] = 0x2;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x25]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x25])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x3, tmp.vregs[0x1]);
         assert_eq!(0x2, tmp.vregs[0x2]);
         assert_eq!(0x0, tmp.vregs[0xf]);
@@ -840,8 +1197,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0xfa;
         tmp.vregs[0x2] = 0x23;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x25]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x25])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xfa - 0x23, tmp.vregs[0x1]);
         assert_eq!(0x23, tmp.vregs[0x2]);
         assert_eq!(0x0, tmp.vregs[0xf]);
@@ -849,8 +1206,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x5;
         tmp.vregs[0x2] = 0x6;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x25]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x25])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xff, tmp.vregs[0x1]);
         assert_eq!(0x6, tmp.vregs[0x2]);
         assert_eq!(0x1, tmp.vregs[0xf]);
@@ -858,8 +1215,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x5;
         tmp.vregs[0x2] = 0x7;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x25]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x25])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xfe, tmp.vregs[0x1]);
         assert_eq!(0x7, tmp.vregs[0x2]);
         assert_eq!(0x1, tmp.vregs[0xf]);
@@ -870,8 +1227,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x2;
         tmp.vregs[0x2] = 0x5;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x27]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x27])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x3, tmp.vregs[0x1]);
         assert_eq!(0x5, tmp.vregs[0x2]);
         assert_eq!(0x0, tmp.vregs[0xf]);
@@ -879,8 +1236,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x23;
         tmp.vregs[0x2] = 0xfa;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x27]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x27])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xfa - 0x23, tmp.vregs[0x1]);
         assert_eq!(0xfa, tmp.vregs[0x2]);
         assert_eq!(0x0, tmp.vregs[0xf]);
@@ -888,8 +1245,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x6;
         tmp.vregs[0x2] = 0x5;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x27]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x27])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xff, tmp.vregs[0x1]);
         assert_eq!(0x5, tmp.vregs[0x2]);
         assert_eq!(0x1, tmp.vregs[0xf]);
@@ -897,8 +1254,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x1] = 0x7;
         tmp.vregs[0x2] = 0x5;
-        tmp.load_program(&Chip8Program::new(&[0x81, 0x27]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x27])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xfe, tmp.vregs[0x1]);
         assert_eq!(0x5, tmp.vregs[0x2]);
         assert_eq!(0x1, tmp.vregs[0xf]);
@@ -908,77 +1265,97 @@ mod tests {
     fn test_exec_SR() {
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0b10101010;
-        tmp.load_program(&Chip8Program::new(&[0x88, 0xa6]));
-        tmp.exec_step(fake_getchar);
-        assert_eq!(0b01010101, tmp.vregs[0xa]);
+        tmp.load_program(&Chip8Program::new(&[0x88, 0xa6])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0b10101010, tmp.vregs[0xa]);
         assert_eq!(0b01010101, tmp.vregs[0x8]);
         assert_eq!(0x0, tmp.vregs[0xf]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0b10101011;
-        tmp.load_program(&Chip8Program::new(&[0x88, 0xa6]));
-        tmp.exec_step(fake_getchar);
-        assert_eq!(0b01010101, tmp.vregs[0xa]);
+        tmp.load_program(&Chip8Program::new(&[0x88, 0xa6])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0b10101011, tmp.vregs[0xa]);
         assert_eq!(0b01010101, tmp.vregs[0x8]);
         assert_eq!(0x1, tmp.vregs[0xf]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0b10101011;
-        tmp.load_program(&Chip8Program::new(&[0x88, 0xa6, 0x88, 0xa6]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x88, 0xa6, 0x88, 0x86])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x1, tmp.vregs[0xf]);
-        tmp.exec_step(fake_getchar);
-        assert_eq!(0b00101010, tmp.vregs[0xa]);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0b10101011, tmp.vregs[0xa]);
         assert_eq!(0b00101010, tmp.vregs[0x8]);
         assert_eq!(0x1, tmp.vregs[0xf]);
+
+        let mut tmp = Chip8State::new();
+        tmp.quirks = Quirks::chip48();
+        tmp.vregs[0x8] = 0b10101011;
+        tmp.vregs[0xa] = 0b00001111;
+        tmp.load_program(&Chip8Program::new(&[0x88, 0xa6])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0b01010101, tmp.vregs[0x8]);
+        assert_eq!(0b00001111, tmp.vregs[0xa]);
+        assert_eq!(0x1, tmp.vregs[0xf]);
     }
 
     #[test]
     fn test_exec_SL() {
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0b10101010;
-        tmp.load_program(&Chip8Program::new(&[0x88, 0xae]));
-        tmp.exec_step(fake_getchar);
-        assert_eq!(0b01010100, tmp.vregs[0xa]);
+        tmp.load_program(&Chip8Program::new(&[0x88, 0xae])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0b10101010, tmp.vregs[0xa]);
         assert_eq!(0b01010100, tmp.vregs[0x8]);
         assert_eq!(0x1, tmp.vregs[0xf]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0b00101010;
-        tmp.load_program(&Chip8Program::new(&[0x88, 0xae]));
-        tmp.exec_step(fake_getchar);
-        assert_eq!(0b01010100, tmp.vregs[0xa]);
+        tmp.load_program(&Chip8Program::new(&[0x88, 0xae])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0b00101010, tmp.vregs[0xa]);
         assert_eq!(0b01010100, tmp.vregs[0x8]);
         assert_eq!(0x0, tmp.vregs[0xf]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0b00101010;
-        tmp.load_program(&Chip8Program::new(&[0x88, 0xae, 0x88, 0xae]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x88, 0xae, 0x88, 0x8e])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0, tmp.vregs[0xf]);
-        tmp.exec_step(fake_getchar);
-        assert_eq!(0b10101000, tmp.vregs[0xa]);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0b00101010, tmp.vregs[0xa]);
         assert_eq!(0b10101000, tmp.vregs[0x8]);
         assert_eq!(0x0, tmp.vregs[0xf]);
+
+        let mut tmp = Chip8State::new();
+        tmp.quirks = Quirks::chip48();
+        tmp.vregs[0x8] = 0b10101011;
+        tmp.vregs[0xa] = 0b00001111;
+        tmp.load_program(&Chip8Program::new(&[0x88, 0xae])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0b01010110, tmp.vregs[0x8]);
+        assert_eq!(0b00001111, tmp.vregs[0xa]);
+        assert_eq!(0x1, tmp.vregs[0xf]);
     }
 
     #[test]
     fn test_exec_JMPR() {
         let mut tmp = Chip8State::new();
-        tmp.load_program(&Chip8Program::new(&[0xba, 0xbc]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xba, 0xbc])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0abc, tmp.pc);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0x0] = 0x2;
-        tmp.load_program(&Chip8Program::new(&[0xba, 0xbc]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xba, 0xbc])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0abc + 0x2, tmp.pc);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0x0] = 0xad;
-        tmp.load_program(&Chip8Program::new(&[0xba, 0xbc]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xba, 0xbc])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x0abc + 0xad, tmp.pc);
     }
 
@@ -986,8 +1363,8 @@ mod tests {
     fn test_exec_RAND() {
         fn gen_rand(mask: u8) -> u8 {
             let mut tmp = Chip8State::new();
-            tmp.load_program(&Chip8Program::new(&[0xca, mask]));
-            tmp.exec_step(fake_getchar);
+            tmp.load_program(&Chip8Program::new(&[0xca, mask])).unwrap();
+            tmp.exec_step(&mut NullIo).unwrap();
             return tmp.vregs[0xa];
         }
 
@@ -1002,39 +1379,176 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_exec_RAND_seed() {
+        fn gen_rands(seed: u64) -> Vec<u8> {
+            let mut tmp = Chip8State::new();
+            tmp.seed_rng(seed);
+            tmp.load_program(&Chip8Program::new(&[0xca, 0xff, 0x12, 0x00])).unwrap();
+            (0..16).map(|_| {
+                tmp.exec_step(&mut NullIo).unwrap();
+                tmp.exec_step(&mut NullIo).unwrap();
+                tmp.vregs[0xa]
+            }).collect()
+        }
+
+        assert_eq!(gen_rands(1234), gen_rands(1234));
+        assert_ne!(gen_rands(1234), gen_rands(4321));
+    }
+
     #[bench]
     fn bench_random(b: &mut Bencher) {
-        b.iter(|| random(0xff));
+        let mut rng = Chip8Rng::new(0);
+        b.iter(|| rng.next_u8());
+    }
+
+    #[test]
+    fn test_exec_SKIPKEQ() {
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0x3] = 0xa;
+        tmp.load_program(&Chip8Program::new(&[0xe3, 0x9e])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0202, tmp.pc);
+
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0x3] = 0xa;
+        tmp.set_key(0xa, true);
+        tmp.load_program(&Chip8Program::new(&[0xe3, 0x9e])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0204, tmp.pc);
+
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0x3] = 0xa;
+        tmp.set_key(0xb, true);
+        tmp.load_program(&Chip8Program::new(&[0xe3, 0x9e])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0202, tmp.pc);
+    }
+
+    #[test]
+    fn test_exec_SKIPKNEQ() {
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0x3] = 0xa;
+        tmp.load_program(&Chip8Program::new(&[0xe3, 0xa1])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0204, tmp.pc);
+
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0x3] = 0xa;
+        tmp.set_key(0xa, true);
+        tmp.load_program(&Chip8Program::new(&[0xe3, 0xa1])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0202, tmp.pc);
+    }
+
+    #[test]
+    fn test_exec_GKEY() {
+        let mut tmp = Chip8State::new();
+        tmp.load_program(&Chip8Program::new(&[0xf3, 0x0a])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0200, tmp.pc);
+        tmp.set_key(0x7, true);
+        tmp.exec_step(&mut NullIo).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0200, tmp.pc);
+        assert_eq!(0x0, tmp.vregs[0x3]);
+        tmp.set_key(0x7, false);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0202, tmp.pc);
+        assert_eq!(0x7, tmp.vregs[0x3]);
+    }
+
+    #[test]
+    fn test_io() {
+        let mut io = TestIo { keys: [false; 16], sound: vec![], frames: 0 };
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0x1] = 0x5;
+        tmp.i = FONT_START;
+        // SSND V1, SKIPKEQ V1, CLS, DRAW V0, V0, #$5, GKEY V2, SSND V0
+        tmp.load_program(&Chip8Program::new(&[
+            0xf1, 0x18, 0xe1, 0x9e, 0x00, 0xe0, 0xd0, 0x05, 0xf2, 0x0a, 0xf0, 0x18,
+        ])).unwrap();
+        tmp.exec_step(&mut io).unwrap();
+        assert_eq!(vec![true], io.sound);
+        io.keys[0x5] = true;
+        tmp.exec_step(&mut io).unwrap();
+        assert_eq!(0x0206, tmp.pc);
+        assert_eq!(0, io.frames);
+        tmp.exec_step(&mut io).unwrap();
+        tmp.exec_step(&mut io).unwrap();
+        io.keys[0x5] = false;
+        tmp.exec_step(&mut io).unwrap();
+        assert_eq!(0x020a, tmp.pc);
+        assert_eq!(0x5, tmp.vregs[0x2]);
+        assert_eq!(1, io.frames);
+        tmp.exec_step(&mut io).unwrap();
+        assert_eq!(vec![true, false], io.sound);
+
+        // Running out is reported on the next sync
+        tmp.sound = 1;
+        tmp.sync_sound(&mut io);
+        tmp.set_clock(Box::new(VirtualClock::new()));
+        tmp.tick_60hz();
+        tmp.sync_sound(&mut io);
+        assert_eq!(vec![true, false, true, false], io.sound);
     }
 
     #[test]
     fn test_exec_GDELAY() {
         let mut tmp = Chip8State::new();
         tmp.delay = 0x21;
-        tmp.load_program(&Chip8Program::new(&[0xfa, 0x07]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xfa, 0x07])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x21, tmp.vregs[0xa]);
 
         let mut tmp = Chip8State::new();
         tmp.delay = 0xfa;
-        tmp.load_program(&Chip8Program::new(&[0xfa, 0x07]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xfa, 0x07])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xfa, tmp.vregs[0xa]);
     }
 
+    #[test]
+    fn test_timers() {
+        let mut tmp = Chip8State::new();
+        tmp.set_clock(Box::new(VirtualClock::new()));
+        tmp.delay = 3;
+        tmp.sound = 1;
+        tmp.update_timers();
+        assert_eq!(3, tmp.delay);
+        tmp.tick_60hz();
+        assert_eq!(2, tmp.delay);
+        assert_eq!(0, tmp.sound);
+        tmp.tick_60hz();
+        tmp.tick_60hz();
+        tmp.tick_60hz();
+        assert_eq!(0, tmp.delay);
+        assert_eq!(4, tmp.timer_updates);
+
+        let mut tmp = Chip8State::new();
+        tmp.set_clock(Box::new(VirtualClock::per_instructions(2)));
+        tmp.delay = 0x10;
+        tmp.load_program(&Chip8Program::new(&[0x12, 0x00])).unwrap();
+        for _ in 0..6 {
+            tmp.exec_step(&mut NullIo).unwrap();
+        }
+        assert_eq!(0x10 - 3, tmp.delay);
+    }
+
     #[test]
     fn test_exec_SDELAY() {
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0x21;
-        tmp.load_program(&Chip8Program::new(&[0xfa, 0x15]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xfa, 0x15])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x21, tmp.delay);
         assert_eq!(0x21, tmp.vregs[0xa]);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0xfa;
-        tmp.load_program(&Chip8Program::new(&[0xfa, 0x15]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xfa, 0x15])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0xfa, tmp.delay);
         assert_eq!(0xfa, tmp.vregs[0xa]);
     }
@@ -1043,15 +1557,15 @@ mod tests {
     fn test_exec_ADDI() {
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0x21;
-        tmp.load_program(&Chip8Program::new(&[0xfa, 0x1e]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xfa, 0x1e])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x21, tmp.i);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0xa] = 0x21;
         tmp.i = 0xda;
-        tmp.load_program(&Chip8Program::new(&[0xfa, 0x1e]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xfa, 0x1e])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0x21 + 0xda, tmp.i);
     }
 
@@ -1062,8 +1576,8 @@ mod tests {
             tmp.vregs[i] = i as u8 * 2;
         }
         tmp.i = 0x520;
-        tmp.load_program(&Chip8Program::new(&[0xf8, 0x55]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xf8, 0x55])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         for i in 0..0x9 {
             assert_eq!(tmp.mem[i as usize + 0x520], i * 2);
         }
@@ -1081,8 +1595,8 @@ mod tests {
             tmp.mem[i as usize + 0x520] = i as u8 * 2;
         }
         tmp.i = 0x520;
-        tmp.load_program(&Chip8Program::new(&[0xf8, 0x65]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xf8, 0x65])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         for i in 0..0x9 {
             assert_eq!(tmp.vregs[i], i as u8 * 2);
         }
@@ -1093,13 +1607,41 @@ mod tests {
         assert_eq!(tmp.i, 0x520 + 0x9);
     }
 
+    #[test]
+    fn test_last_accesses() {
+        let mut tmp = Chip8State::new();
+        tmp.i = 0x521;
+        tmp.vregs[0x5] = 153;
+        // BCD V5, RLOAD V1, ADD V0, #$1
+        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33, 0xf1, 0x65, 0x70, 0x01])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(
+            &[
+                MemAccess { addr: 0x521, value: 1, write: true, stack: false },
+                MemAccess { addr: 0x522, value: 5, write: true, stack: false },
+                MemAccess { addr: 0x523, value: 3, write: true, stack: false },
+            ],
+            tmp.last_accesses()
+        );
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(
+            &[
+                MemAccess { addr: 0x521, value: 1, write: false, stack: false },
+                MemAccess { addr: 0x522, value: 5, write: false, stack: false },
+            ],
+            tmp.last_accesses()
+        );
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert!(tmp.last_accesses().is_empty());
+    }
+
     #[test]
     fn test_exec_BCD() {
         let mut tmp = Chip8State::new();
         tmp.i = 0x521;
         tmp.vregs[0x5] = 153;
-        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(1, tmp.mem[0x521]);
         assert_eq!(5, tmp.mem[0x522]);
         assert_eq!(3, tmp.mem[0x523]);
@@ -1108,8 +1650,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.i = 0x521;
         tmp.vregs[0x5] = 003;
-        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0, tmp.mem[0x521]);
         assert_eq!(0, tmp.mem[0x522]);
         assert_eq!(3, tmp.mem[0x523]);
@@ -1118,8 +1660,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.i = 0x521;
         tmp.vregs[0x5] = 255;
-        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(2, tmp.mem[0x521]);
         assert_eq!(5, tmp.mem[0x522]);
         assert_eq!(5, tmp.mem[0x523]);
@@ -1128,8 +1670,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         tmp.i = 0x521;
         tmp.vregs[0x5] = 32;
-        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(0, tmp.mem[0x521]);
         assert_eq!(3, tmp.mem[0x522]);
         assert_eq!(2, tmp.mem[0x523]);
@@ -1140,28 +1682,417 @@ mod tests {
     fn test_exec_SPRITE() {
         let mut tmp = Chip8State::new();
         tmp.vregs[0x5] = 0x0;
-        tmp.load_program(&Chip8Program::new(&[0xf5, 0x29]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xf5, 0x29])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(FONT_START, tmp.i);
 
         let mut tmp = Chip8State::new();
         tmp.vregs[0x5] = 0xa;
-        tmp.load_program(&Chip8Program::new(&[0xf5, 0x29]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xf5, 0x29])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(FONT_START + 5 * 0xa, tmp.i);
     }
 
+    #[test]
+    fn test_load_font() {
+        let tmp = Chip8State::new();
+        for c in 0..0x10 {
+            let start = FONT_START as usize + c * 5;
+            assert_eq!(&font::DEFAULT[c * 5..c * 5 + 5], &tmp.mem[start..start + 5]);
+        }
+
+        let mut tmp = Chip8State::new();
+        tmp.load_font(&font::DREAM_6800);
+        let start = FONT_START as usize;
+        assert_eq!(&font::DREAM_6800[..], &tmp.mem[start..start + FONT_SIZE]);
+        // The big font directly follows and must be left alone
+        let start = BIG_FONT_START as usize;
+        assert_eq!(&font::BIG[..], &tmp.mem[start..start + BIG_FONT_SIZE]);
+    }
+
     #[test]
     fn test_exec_CLS() {
         let mut tmp = Chip8State::new();
-        for i in 0xf00..0xfff + 1 {
-            tmp.mem[i as usize] = 0b10101010;
+        for y in 0..tmp.screen.height() {
+            for x in (0..tmp.screen.width()).filter(|x| x % 2 == 0) {
+                tmp.screen.xor_pixel(x, y, 1);
+            }
         }
-        tmp.load_program(&Chip8Program::new(&[0x00, 0xe0]));
-        tmp.exec_step(fake_getchar);
-        for i in 0xf00..0xfff + 1 {
-            assert_eq!(tmp.mem[i as usize], 0x00);
+        tmp.load_program(&Chip8Program::new(&[0x00, 0xe0])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(Framebuffer::new(LORES_WIDTH, LORES_HEIGHT), tmp.screen);
+    }
+
+    #[test]
+    fn test_display_ram() {
+        // The old display area at 0xF00 is plain RAM now
+        let mut tmp = Chip8State::new();
+        tmp.quirks.display_wait = false;
+        tmp.vregs = [0xff; 16];
+        tmp.i = 0xf00;
+        // RDUMP VF, CLS
+        tmp.load_program(&Chip8Program::new(&[0xff, 0x55, 0x00, 0xe0])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert!(!tmp.screen.is_dirty());
+        assert!(!tmp.pixel_on(0, 0));
+        tmp.write_pixel(0, 0, true);
+        assert!(tmp.pixel_on(0, 0));
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert!(!tmp.pixel_on(0, 0));
+        assert_eq!([0xff; 16], tmp.mem[0xf00..0xf10]);
+    }
+
+    #[test]
+    fn test_exec_DRAW() {
+        // Glyph "0" is 0xf0, 0x90, 0x90, 0x90, 0xf0
+        let mut tmp = Chip8State::new();
+        tmp.quirks.display_wait = false;
+        tmp.i = FONT_START;
+        tmp.vregs[0x1] = 2;
+        tmp.vregs[0x2] = 3;
+        tmp.load_program(&Chip8Program::new(&[0xd1, 0x25, 0xd1, 0x25])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0, tmp.vregs[0xf]);
+        for x in 2..6 {
+            assert!(tmp.pixel_on(x, 3));
+            assert!(tmp.pixel_on(x, 7));
         }
+        assert!(tmp.pixel_on(2, 5));
+        assert!(!tmp.pixel_on(3, 5));
+        assert!(tmp.pixel_on(5, 5));
+        assert!(!tmp.pixel_on(6, 5));
+        assert!(!tmp.pixel_on(2, 8));
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x1, tmp.vregs[0xf]);
+        for y in 0..tmp.screen.height() {
+            for x in 0..tmp.screen.width() {
+                assert!(!tmp.pixel_on(x, y));
+            }
+        }
+
+        let mut tmp = Chip8State::new();
+        tmp.quirks.display_wait = false;
+        tmp.i = FONT_START;
+        tmp.vregs[0x1] = 2;
+        tmp.vregs[0x2] = 3;
+        tmp.load_program(&Chip8Program::new(&[0xd1, 0x25, 0xd2, 0x15])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x1, tmp.vregs[0xf]);
+        assert!(tmp.pixel_on(2, 3));
+        assert!(!tmp.pixel_on(3, 3));
+        assert!(tmp.pixel_on(6, 3));
+    }
+
+    #[test]
+    fn test_exec_DRAW_edges() {
+        let mut tmp = Chip8State::new();
+        tmp.i = FONT_START;
+        tmp.vregs[0x1] = 62;
+        tmp.vregs[0x2] = 30;
+        tmp.load_program(&Chip8Program::new(&[0xd1, 0x25])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0, tmp.vregs[0xf]);
+        assert!(tmp.pixel_on(62, 30));
+        assert!(tmp.pixel_on(63, 30));
+        assert!(tmp.pixel_on(62, 31));
+        assert!(!tmp.pixel_on(63, 31));
+        assert!(!tmp.pixel_on(0, 30));
+        assert!(!tmp.pixel_on(62, 0));
+
+        let mut tmp = Chip8State::new();
+        tmp.i = FONT_START;
+        tmp.vregs[0x1] = 64 + 1;
+        tmp.vregs[0x2] = 32 + 1;
+        tmp.load_program(&Chip8Program::new(&[0xd1, 0x25])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert!(tmp.pixel_on(1, 1));
+        assert!(tmp.pixel_on(4, 5));
+        assert!(!tmp.pixel_on(0, 0));
+    }
+
+    #[test]
+    fn test_exec_HIGH_LOW() {
+        let mut tmp = Chip8State::new();
+        tmp.screen.xor_pixel(1, 1, 1);
+        tmp.load_program(&Chip8Program::new(&[0x00, 0xff, 0x00, 0xfe])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(Framebuffer::new(HIRES_WIDTH, HIRES_HEIGHT), tmp.screen);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(Framebuffer::new(LORES_WIDTH, LORES_HEIGHT), tmp.screen);
+    }
+
+    #[test]
+    fn test_exec_DRAW_16x16() {
+        let mut tmp = Chip8State::new();
+        tmp.quirks.display_wait = false;
+        tmp.i = 0x300;
+        tmp.mem[0x300] = 0x80;
+        tmp.mem[0x301] = 0x01;
+        tmp.mem[0x31f] = 0xff;
+        tmp.vregs[0x1] = 100;
+        tmp.vregs[0x2] = 40;
+        // HIGH, DRAW V1, V2, #$0
+        tmp.load_program(&Chip8Program::new(&[0x00, 0xff, 0xd1, 0x20])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0, tmp.vregs[0xf]);
+        assert!(tmp.pixel_on(100, 40));
+        assert!(!tmp.pixel_on(101, 40));
+        assert!(tmp.pixel_on(115, 40));
+        assert!(!tmp.pixel_on(107, 55));
+        assert!(tmp.pixel_on(108, 55));
+        assert!(tmp.pixel_on(115, 55));
+        assert!(!tmp.pixel_on(116, 55));
+    }
+
+    #[test]
+    fn test_exec_scroll() {
+        let mut tmp = Chip8State::new();
+        tmp.screen.xor_pixel(10, 10, 1);
+        // SCD #$3, SCR, SCL, SCL
+        tmp.load_program(&Chip8Program::new(&[0x00, 0xc3, 0x00, 0xfb, 0x00, 0xfc, 0x00, 0xfc])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert!(tmp.pixel_on(10, 13));
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert!(tmp.pixel_on(14, 13));
+        tmp.exec_step(&mut NullIo).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert!(tmp.pixel_on(6, 13));
+        assert!(!tmp.pixel_on(14, 13));
+    }
+
+    #[test]
+    fn test_exec_EXIT() {
+        let mut tmp = Chip8State::new();
+        tmp.load_program(&Chip8Program::new(&[0x00, 0xfd, 0x70, 0x01])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert!(tmp.halted);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x200, tmp.pc);
+        assert_eq!(0x0, tmp.vregs[0x0]);
+    }
+
+    #[test]
+    fn test_exec_XSPRITE() {
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0x3] = 0xa;
+        tmp.load_program(&Chip8Program::new(&[0xf3, 0x30])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(BIG_FONT_START + 10 * 0xa, tmp.i);
+        let start = tmp.i as usize;
+        assert_eq!(&font::BIG[100..110], &tmp.mem[start..start + 10]);
+    }
+
+    #[test]
+    fn test_exec_SRPL_LRPL() {
+        let mut tmp = Chip8State::new();
+        for i in 0..16 {
+            tmp.vregs[i] = i as u8 + 1;
+        }
+        tmp.load_program(&Chip8Program::new(&[0xf3, 0x75, 0xf7, 0x85])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!([1, 2, 3, 4, 0, 0, 0, 0], tmp.rpl[..8]);
+        tmp.vregs = [0xff; 16];
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!([1, 2, 3, 4, 0, 0, 0, 0, 0xff], tmp.vregs[..9]);
+    }
+
+    #[test]
+    fn test_exec_LONGI() {
+        let mut tmp = Chip8State::new();
+        assert_eq!(0x10000, tmp.mem.len());
+        tmp.load_program(&Chip8Program::new(&[0xf0, 0x00, 0xfe, 0xdc, 0xf0, 0x65])).unwrap();
+        tmp.mem[0xfedc] = 0x42;
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0xfedc, tmp.i);
+        assert_eq!(0x204, tmp.pc);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x42, tmp.vregs[0x0]);
+
+        // Skips step over both words
+        let mut tmp = Chip8State::new();
+        tmp.load_program(&Chip8Program::new(&[0x30, 0x00, 0xf0, 0x00, 0x12, 0x34])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x206, tmp.pc);
+    }
+
+    #[test]
+    fn test_exec_SAVER_LOADR() {
+        let mut tmp = Chip8State::new();
+        for i in 0..16 {
+            tmp.vregs[i] = i as u8;
+        }
+        tmp.i = 0x300;
+        // SAVER V2, V4, SAVER V4, V2, LOADR V8, VA
+        tmp.load_program(&Chip8Program::new(&[0x52, 0x42, 0x54, 0x22, 0x58, 0xa3])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!([2, 3, 4], tmp.mem[0x300..0x303]);
+        assert_eq!(0x300, tmp.i);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!([4, 3, 2], tmp.mem[0x300..0x303]);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!([4, 3, 2, 0xb], tmp.vregs[0x8..0xc]);
+    }
+
+    #[test]
+    fn test_exec_PLANE() {
+        let mut tmp = Chip8State::new();
+        tmp.quirks.display_wait = false;
+        tmp.i = 0x300;
+        tmp.mem[0x300] = 0x80;
+        tmp.mem[0x301] = 0xc0;
+        // PLANE #$3, DRAW V0, V0, #$1, PLANE #$2, CLS
+        tmp.load_program(&Chip8Program::new(&[0xf3, 0x01, 0xd0, 0x01, 0xf2, 0x01, 0x00, 0xe0])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x3, tmp.plane);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x3, tmp.screen.planes(0, 0));
+        assert_eq!(0x2, tmp.screen.planes(1, 0));
+        tmp.exec_step(&mut NullIo).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x1, tmp.screen.planes(0, 0));
+        assert_eq!(0x0, tmp.screen.planes(1, 0));
+    }
+
+    #[test]
+    fn test_exec_AUDIO_PITCH() {
+        let mut tmp = Chip8State::new();
+        assert_eq!(64, tmp.pitch);
+        tmp.i = 0x300;
+        for i in 0..16 {
+            tmp.mem[0x300 + i] = 0xf0 | i as u8;
+        }
+        tmp.vregs[0x4] = 112;
+        tmp.load_program(&Chip8Program::new(&[0xf0, 0x02, 0xf4, 0x3a])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(tmp.mem[0x300..0x310], tmp.audio_pattern[..]);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(112, tmp.pitch);
+    }
+
+    #[test]
+    fn test_save_state() {
+        let mut tmp = Chip8State::new();
+        tmp.set_clock(Box::new(VirtualClock::new()));
+        tmp.seed_rng(7);
+        tmp.quirks = Quirks::schip();
+        tmp.vregs[0x3] = 0x10;
+        tmp.vregs[0x4] = 0x08;
+        tmp.i = FONT_START;
+        tmp.delay = 9;
+        tmp.sound = 4;
+        tmp.set_key(0xb, true);
+        // HIGH, DRAW V3, V4, #$5, RAND V0, #$FF, RAND V1, #$FF
+        tmp.load_program(&Chip8Program::new(&[0x00, 0xff, 0xd3, 0x45, 0xc0, 0xff, 0xc1, 0xff])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        tmp.tick_60hz();
+
+        let saved = tmp.save_state();
+        let mut restored = Chip8State::new();
+        restored.load_state(&saved).unwrap();
+        assert_eq!(tmp, restored);
+        assert_eq!(tmp.quirks, restored.quirks);
+        assert_eq!(tmp.rng_seed(), restored.rng_seed());
+        assert_eq!(tmp.timer_updates, restored.timer_updates);
+        assert_eq!(saved, restored.save_state());
+
+        // Both continue identically, including the random numbers
+        for _ in 0..2 {
+            tmp.exec_step(&mut NullIo).unwrap();
+            restored.exec_step(&mut NullIo).unwrap();
+        }
+        tmp.tick_60hz();
+        restored.tick_60hz();
+        assert_eq!(tmp, restored);
+        assert_eq!(7, restored.delay);
+    }
+
+    #[test]
+    fn test_load_state_invalid() {
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0x0] = 0x42;
+        let saved = tmp.save_state();
+        let mut other = Chip8State::new();
+        assert_eq!(Err(SaveStateError::BadMagic), other.load_state(b"nope"));
+        assert_eq!(Err(SaveStateError::Truncated), other.load_state(&saved[..saved.len() - 1]));
+        let mut longer = saved.clone();
+        longer.push(0);
+        assert_eq!(Err(SaveStateError::Invalid("length")), other.load_state(&longer));
+        assert_eq!(0x0, other.vregs[0x0]);
+    }
+
+    #[test]
+    fn test_quirks() {
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0xf] = 0x1;
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x21])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0, tmp.vregs[0xf]);
+
+        let mut tmp = Chip8State::new();
+        tmp.quirks = Quirks::chip48();
+        tmp.vregs[0xf] = 0x1;
+        tmp.load_program(&Chip8Program::new(&[0x81, 0x22])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x1, tmp.vregs[0xf]);
+
+        let mut tmp = Chip8State::new();
+        tmp.quirks = Quirks::chip48();
+        tmp.vregs[0x0] = 0x10;
+        tmp.vregs[0xa] = 0x2;
+        tmp.load_program(&Chip8Program::new(&[0xba, 0xbc])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0abc + 0x2, tmp.pc);
+
+        let mut tmp = Chip8State::new();
+        tmp.quirks = Quirks::chip48();
+        tmp.i = 0x520;
+        tmp.load_program(&Chip8Program::new(&[0xf8, 0x55, 0xf8, 0x65])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x528, tmp.i);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x530, tmp.i);
+
+        let mut tmp = Chip8State::new();
+        tmp.quirks = Quirks::schip();
+        tmp.i = 0x520;
+        tmp.load_program(&Chip8Program::new(&[0xf8, 0x55, 0xf8, 0x65])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x520, tmp.i);
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x520, tmp.i);
+
+        let mut tmp = Chip8State::new();
+        tmp.quirks = Quirks::xochip();
+        tmp.i = FONT_START;
+        tmp.vregs[0x1] = 62;
+        tmp.vregs[0x2] = 30;
+        tmp.load_program(&Chip8Program::new(&[0xd1, 0x25])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert!(tmp.pixel_on(62, 30));
+        assert!(tmp.pixel_on(63, 30));
+        assert!(tmp.pixel_on(0, 30));
+        assert!(tmp.pixel_on(1, 30));
+        assert!(tmp.pixel_on(62, 0));
+        assert!(tmp.pixel_on(62, 2));
+    }
+
+    #[test]
+    fn test_display_wait() {
+        let mut tmp = Chip8State::new();
+        tmp.set_clock(Box::new(VirtualClock::new()));
+        tmp.i = FONT_START;
+        tmp.load_program(&Chip8Program::new(&[0xd1, 0x25, 0xd1, 0x25])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0202, tmp.pc);
+        assert!(tmp.pixel_on(0, 0));
+        tmp.tick_60hz();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(0x0204, tmp.pc);
+        assert!(!tmp.pixel_on(0, 0));
     }
 
     #[test]
@@ -1169,8 +2100,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         assert_eq!(tmp.sound, 0x00);
         tmp.vregs[0xb] = 0x32;
-        tmp.load_program(&Chip8Program::new(&[0xfb, 0x18]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0xfb, 0x18])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(tmp.sound, 0x32);
     }
 
@@ -1179,8 +2110,8 @@ mod tests {
         let mut tmp = Chip8State::new();
         assert_eq!(tmp.pc, 0x200);
         assert_eq!(tmp.sp, 0);
-        tmp.load_program(&Chip8Program::new(&[0x24, 0x56]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x24, 0x56])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(tmp.pc, 0x456);
         assert_eq!(tmp.sp, 1);
         assert_eq!(tmp.mem[STACK_START as usize], 0x02);
@@ -1189,9 +2120,9 @@ mod tests {
         let mut tmp = Chip8State::new();
         assert_eq!(tmp.pc, 0x200);
         assert_eq!(tmp.sp, 0);
-        tmp.load_program(&Chip8Program::new(&[0x00, 0x00, 0x24, 0x56]));
+        tmp.load_program(&Chip8Program::new(&[0x00, 0x00, 0x24, 0x56])).unwrap();
         tmp.pc += 2;
-        tmp.exec_step(fake_getchar);
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(tmp.pc, 0x456);
         assert_eq!(tmp.sp, 1);
         assert_eq!(tmp.mem[STACK_START as usize], 0x02);
@@ -1200,28 +2131,84 @@ mod tests {
         let mut tmp = Chip8State::new();
         assert_eq!(tmp.pc, 0x200);
         assert_eq!(tmp.sp, 0);
-        tmp.load_program(&Chip8Program::new(&[0x22, 0x02, 0x22, 0x34]));
-        tmp.exec_step(fake_getchar);
+        tmp.load_program(&Chip8Program::new(&[0x22, 0x02, 0x22, 0x34])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(tmp.pc, 0x202);
         assert_eq!(tmp.sp, 1);
         assert_eq!(tmp.mem[STACK_START as usize], 0x02);
         assert_eq!(tmp.mem[STACK_START as usize + 1], 0x00);
-        tmp.exec_step(fake_getchar);
+        tmp.exec_step(&mut NullIo).unwrap();
         assert_eq!(tmp.pc, 0x0234);
         assert_eq!(tmp.sp, 2);
         assert_eq!(tmp.mem[STACK_START as usize + 2], 0x02);
         assert_eq!(tmp.mem[STACK_START as usize + 3], 0x02);
     }
 
+    #[test]
+    fn test_faults() {
+        let mut tmp = Chip8State::new();
+        tmp.load_program(&Chip8Program::new(&[0xff, 0xff])).unwrap();
+        assert_eq!(
+            Err(Chip8Fault { pc: 0x200, opcode: 0xffff, kind: FaultKind::InvalidOpcode }),
+            tmp.exec_step(&mut NullIo)
+        );
+
+        let mut tmp = Chip8State::new();
+        tmp.load_program(&Chip8Program::new(&[0x22, 0x00])).unwrap();
+        for _ in 0..16 {
+            tmp.exec_step(&mut NullIo).unwrap();
+        }
+        assert_eq!(
+            Err(Chip8Fault { pc: 0x200, opcode: 0x2200, kind: FaultKind::StackOverflow }),
+            tmp.exec_step(&mut NullIo)
+        );
+
+        let mut tmp = Chip8State::new();
+        tmp.load_program(&Chip8Program::new(&[0x00, 0xee])).unwrap();
+        assert_eq!(FaultKind::StackUnderflow, tmp.exec_step(&mut NullIo).unwrap_err().kind);
+
+        let mut tmp = Chip8State::new();
+        tmp.vregs[0x5] = 0x10;
+        tmp.load_program(&Chip8Program::new(&[0xf5, 0x29])).unwrap();
+        assert_eq!(FaultKind::InvalidFontChar(0x10), tmp.exec_step(&mut NullIo).unwrap_err().kind);
+
+        let mut tmp = Chip8State::new();
+        tmp.i = 0xfffe;
+        tmp.load_program(&Chip8Program::new(&[0xf5, 0x33])).unwrap();
+        assert_eq!(FaultKind::MemoryOutOfBounds(0xfffe), tmp.exec_step(&mut NullIo).unwrap_err().kind);
+        assert_eq!(0x00, tmp.mem[0xfffe]);
+
+        let mut tmp = Chip8State::new();
+        tmp.i = 0xfff8;
+        tmp.load_program(&Chip8Program::new(&[0xff, 0x65])).unwrap();
+        assert_eq!(FaultKind::MemoryOutOfBounds(0xfff8), tmp.exec_step(&mut NullIo).unwrap_err().kind);
+
+        let mut tmp = Chip8State::new();
+        tmp.pc = 0xffff;
+        assert_eq!(FaultKind::MemoryOutOfBounds(0xffff), tmp.exec_step(&mut NullIo).unwrap_err().kind);
+    }
+
     #[test]
     fn test_exec_RET() {
         let mut tmp = Chip8State::new();
         tmp.mem[STACK_START as usize] = 0x05;
         tmp.mem[STACK_START as usize + 1] = 0x67;
         tmp.sp = 1;
-        tmp.load_program(&Chip8Program::new(&[0x00, 0xee]));
-        tmp.exec_step(fake_getchar);
-        assert_eq!(tmp.pc, 0x0567);
+        tmp.load_program(&Chip8Program::new(&[0x00, 0xee])).unwrap();
+        tmp.exec_step(&mut NullIo).unwrap();
+        assert_eq!(tmp.pc, 0x0569);
         assert_eq!(tmp.sp, 0);
     }
+
+    #[test]
+    fn test_exec_CALL_RET() {
+        // CALL $206, ADD V0, #$1, EXIT, RET
+        let mut tmp = Chip8State::new();
+        tmp.load_program(&Chip8Program::new(&[0x22, 0x06, 0x70, 0x01, 0x00, 0xfd, 0x00, 0xee])).unwrap();
+        for _ in 0..4 {
+            tmp.exec_step(&mut NullIo).unwrap();
+        }
+        assert!(tmp.halted);
+        assert_eq!(0x1, tmp.vregs[0x0]);
+    }
 }
@@ -0,0 +1,162 @@
+use state::Chip8State;
+use std::io;
+use std::io::Write;
+
+/// Bumped whenever the record layout changes.
+pub const TRACE_VERSION: u8 = 1;
+
+/// The registers compared before and after an instruction.
+#[derive(Clone, Copy)]
+struct Registers {
+    vregs: [u8; 16],
+    i: u16,
+    sp: u8,
+}
+
+impl Registers {
+    fn of(state: &Chip8State) -> Registers {
+        Registers { vregs: state.vregs, i: state.i, sp: state.sp }
+    }
+
+    /// Names and before/after values of the registers that differ in `after`.
+    fn changes(&self, after: &Registers) -> Vec<(String, u16, u16)> {
+        let mut changes = vec![];
+        for v in 0..16 {
+            if self.vregs[v] != after.vregs[v] {
+                changes.push((format!("V{:X}", v), self.vregs[v] as u16, after.vregs[v] as u16));
+            }
+        }
+        if self.i != after.i {
+            changes.push(("I".to_string(), self.i, after.i));
+        }
+        if self.sp != after.sp {
+            changes.push(("SP".to_string(), self.sp as u16, after.sp as u16));
+        }
+        changes
+    }
+}
+
+/// An instruction about to run, completed once the next one starts.
+struct Pending {
+    pc: u16,
+    word: u16,
+    asm: String,
+    before: Registers,
+    delay: u8,
+    sound: u8,
+}
+
+/// Writes one JSON record per executed instruction, after a header with the RNG seed.
+///
+/// `before_instruction` is called ahead of every instruction, like `Debugger`. The record for an
+/// instruction is written when the next one starts, so call `finish` after the last one.
+pub struct Tracer<W: Write> {
+    out: W,
+    cycle: u64,
+    pending: Option<Pending>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(mut out: W, state: &Chip8State) -> Tracer<W> {
+        let header = writeln!(out, "{{\"version\":{},\"seed\":{}}}", TRACE_VERSION, state.rng_seed());
+        Tracer { out, cycle: 0, pending: None, error: header.err() }
+    }
+
+    pub fn before_instruction(&mut self, state: &Chip8State) {
+        self.complete(state);
+        let word = state.get_next_word().unwrap_or(0);
+        self.pending = Some(Pending {
+            pc: state.pc,
+            word,
+            asm: state.get_next_opcode().map_or("[UNDEFINED]".to_string(), |oc| oc.to_asm()),
+            before: Registers::of(state),
+            delay: state.delay,
+            sound: state.sound,
+        });
+    }
+
+    /// Writes the last record and flushes, returning the first write error if there was one.
+    pub fn finish(&mut self, state: &Chip8State) -> Result<(), io::Error> {
+        self.complete(state);
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+
+    /// Writes the record of the last instruction now, for when the state is about to change
+    /// outside of instructions, such as a rewind or loading a save state.
+    pub fn complete(&mut self, state: &Chip8State) {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return,
+        };
+        let changes: Vec<String> = pending
+            .before
+            .changes(&Registers::of(state))
+            .iter()
+            .map(|&(ref name, before, after)| format!("\"{}\":[{},{}]", name, before, after))
+            .collect();
+        let writes: Vec<String> = state
+            .last_accesses()
+            .iter()
+            .filter(|a| a.write)
+            .map(|a| format!("[{},{}]", a.addr, a.value))
+            .collect();
+        let record = writeln!(
+            self.out,
+            "{{\"cycle\":{},\"pc\":{},\"word\":{},\"asm\":\"{}\",\"changes\":{{{}}},\"i\":{},\"writes\":[{}],\"dt\":{},\"st\":{}}}",
+            self.cycle,
+            pending.pc,
+            pending.word,
+            pending.asm,
+            changes.join(","),
+            pending.before.i,
+            writes.join(","),
+            pending.delay,
+            pending.sound
+        );
+        if let Err(e) = record {
+            self.error.get_or_insert(e);
+        }
+        self.cycle += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::VirtualClock;
+    use io::NullIo;
+    use program::Chip8Program;
+
+    #[test]
+    fn test_trace() {
+        let mut state = Chip8State::new();
+        state.set_clock(Box::new(VirtualClock::new()));
+        state.seed_rng(42);
+        state.i = 0x300;
+        state.delay = 3;
+        // MOV V5, #$7B, BCD V5
+        state.load_program(&Chip8Program::new(&[0x65, 0x7b, 0xf5, 0x33])).unwrap();
+        let mut out = vec![];
+        {
+            let mut tracer = Tracer::new(&mut out, &state);
+            for _ in 0..2 {
+                tracer.before_instruction(&state);
+                state.exec_step(&mut NullIo).unwrap();
+            }
+            tracer.finish(&state).unwrap();
+        }
+        let lines: Vec<&str> = ::std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(
+            vec![
+                "{\"version\":1,\"seed\":42}",
+                "{\"cycle\":0,\"pc\":512,\"word\":25979,\"asm\":\"MOV V5, #$7B\",\"changes\":{\"V5\":[0,123]},\"i\":768,\"writes\":[],\"dt\":3,\"st\":0}",
+                "{\"cycle\":1,\"pc\":514,\"word\":62771,\"asm\":\"BCD V5\",\"changes\":{},\"i\":768,\"writes\":[[768,1],[769,2],[770,3]],\"dt\":3,\"st\":0}",
+            ],
+            lines
+        );
+    }
+}